@@ -0,0 +1,74 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use flatcrawl_crawler::dedup::Dedup;
+use flatcrawl_crawler::models::{City, Flat, FlatData};
+
+const SOURCES: [&str; 5] = [
+  "immoscout",
+  "immowelt",
+  "sueddeutsche",
+  "wggesucht",
+  "wohnungsboerse",
+];
+const CITIES: [&str; 4] = ["Munich", "Wuerzburg", "Augsburg", "Kempten"];
+
+fn flats(count: usize) -> Vec<Flat> {
+  (0..count)
+    .map(|i| {
+      Flat::new(
+        SOURCES[i % SOURCES.len()].to_owned(),
+        City::new(CITIES[i % CITIES.len()]),
+      )
+      .fill(&FlatData {
+        rent: 500. + i as f32,
+        squaremeters: 50.,
+        address: format!("Street {}", i),
+        title: format!("Nice flat number {} with balcony!", i),
+        externalid: format!("{}", i),
+        rooms: 2.,
+        ..Default::default()
+      })
+    })
+    .collect()
+}
+
+fn remember(c: &mut Criterion) {
+  let mut group = c.benchmark_group("remember");
+  for count in [1_000, 10_000, 50_000].iter() {
+    let flats = flats(*count);
+    group.bench_with_input(BenchmarkId::from_parameter(count), &flats, |b, flats| {
+      b.iter(|| {
+        let mut dedup = Dedup::new();
+        for flat in flats {
+          dedup.remember(flat);
+        }
+        dedup
+      })
+    });
+  }
+  group.finish();
+}
+
+fn is_new(c: &mut Criterion) {
+  let mut group = c.benchmark_group("is_new");
+  for count in [1_000, 10_000, 50_000].iter() {
+    let known = flats(*count);
+    let mut dedup = Dedup::new();
+    for flat in known.iter() {
+      dedup.remember(flat);
+    }
+    // one cycle worth of listings, half of them already known
+    let current = flats(*count + 500)
+      .into_iter()
+      .skip(*count - 500)
+      .collect::<Vec<_>>();
+    group.bench_with_input(
+      BenchmarkId::from_parameter(count),
+      &current,
+      |b, current| b.iter(|| current.iter().filter(|flat| dedup.is_new(flat)).count()),
+    );
+  }
+  group.finish();
+}
+
+criterion_group!(benches, remember, is_new);
+criterion_main!(benches);
@@ -1,41 +1,1155 @@
-use config::{Config, File};
+use crate::crawlers::{Config as CrawlConfig, CrawlerImpl};
+use crate::http::RequestSettings;
+use crate::models::{Cities, CityInfo, Encoding};
+use crate::ratelimit::{RateLimit, RateLimits};
+use crate::schedule::ScheduleSettings;
+use crate::sinks::{AmqpConfig, SinkConfig, SinkEntry};
+use config::{Config, ConfigError, Environment, File, Value};
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-#[derive(Clone, Debug)]
-pub struct AmqpConfig {
-  pub host: String,
-  pub queue: String,
-  pub username: String,
-  pub password: String,
+/// Everything that is wrong with the configuration, so that it can be fixed
+/// at once. The message lists one problem per line.
+#[derive(Debug)]
+pub struct Error {
+  pub message: String,
+  pub problems: Vec<Problem>,
+}
+
+impl Error {
+  fn new(problems: Vec<Problem>) -> Error {
+    Error {
+      message: problems
+        .iter()
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>()
+        .join("\n"),
+      problems,
+    }
+  }
+}
+
+/// A key that is missing or has an invalid value.
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+  /// the key or the entry it belongs to, like `amqp.host` or `target #2`
+  pub key: String,
+  pub reason: String,
+}
+
+impl Problem {
+  fn new(key: &str, reason: String) -> Problem {
+    Problem {
+      key: key.to_owned(),
+      reason,
+    }
+  }
+}
+
+impl fmt::Display for Problem {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}: {}", self.key, self.reason)
+  }
 }
 
 #[derive(Clone, Debug)]
 pub struct ApplicationConfig {
   pub test: bool,
-  pub thread_count: i32,
+  /// how many targets will be crawled at the same time
+  pub concurrency: usize,
+  /// how long running crawls may take to finish, once the crawler has been
+  /// asked to shut down
+  pub shutdown_timeout: Duration,
+  /// how long storing and sending the flats found so far may take after
+  /// that, before the process exits anyway
+  pub exit_timeout: Duration,
   pub nominatim_url: String,
-  pub amqp_config: AmqpConfig,
+  pub store_path: String,
+  pub request: RequestSettings,
+  pub rate_limits: RateLimits,
+  /// where new flats are sent to
+  pub sinks: Vec<SinkEntry>,
+  pub cities: Cities,
+  pub targets: Vec<CrawlConfig>,
+}
+
+/// A `[[target]]` entry as it has been written down in the configuration.
+#[derive(Debug, Deserialize)]
+struct TargetConfig {
+  crawler: Option<String>,
+  city: Option<String>,
+  host: Option<String>,
+  path: Option<String>,
+  encoding: Option<String>,
+  max_pages: Option<u32>,
+  stop_after_known: Option<u32>,
+  details: Option<bool>,
+  crawl_timeout: Option<u64>,
+  #[serde(flatten)]
+  request: RequestSettings,
+  #[serde(flatten)]
+  schedule: ScheduleSettings,
+}
+
+/// Settings that apply to every target, unless it sets them itself.
+#[derive(Debug, Default)]
+struct TargetDefaults {
+  request: RequestSettings,
+  /// in seconds
+  crawl_timeout: Option<u64>,
+  schedule: ScheduleSettings,
+  /// schedules of the sources by the names of their crawlers
+  source_schedules: HashMap<String, ScheduleSettings>,
+}
+
+/// A `[[source]]` entry that sets the schedule of all targets of a crawler.
+#[derive(Debug, Deserialize)]
+struct SourceConfig {
+  name: Option<String>,
+  #[serde(flatten)]
+  schedule: ScheduleSettings,
 }
 
-pub fn read() -> ApplicationConfig {
+const DEFAULT_CRAWL_TIMEOUT_IN_SECONDS: u64 = 240;
+const DEFAULT_SHUTDOWN_TIMEOUT_IN_SECONDS: u64 = 5;
+const DEFAULT_EXIT_TIMEOUT_IN_SECONDS: u64 = 30;
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org/search";
+const DEFAULT_STORE_PATH: &str = "flatcrawl.db";
+/// environment variables starting with it override keys of the configuration
+const ENV_PREFIX: &str = "FLATCRAWL";
+/// separates the sections of keys within environment variables
+const ENV_SEPARATOR: &str = "__";
+/// extensions of the formats that the configuration can be written in
+const EXTENSIONS: [&str; 6] = ["toml", "json", "yaml", "yml", "hjson", "ini"];
+/// keys ending with it name a file to read the value of the key from
+const FILE_SUFFIX: &str = "_file";
+
+/// A `[[host]]` entry that sets the rate limit of a single host.
+#[derive(Debug, Deserialize)]
+struct HostConfig {
+  name: Option<String>,
+  #[serde(flatten)]
+  limit: RateLimit,
+}
+
+/// Reads the configuration file at `path`, whose extension may be left out.
+/// The targets can also be kept in a separate `targets` file next to it.
+///
+/// Every key can be overridden by an environment variable, like
+/// `FLATCRAWL_NOMINATIM_URL` or `FLATCRAWL_AMQP__PASSWORD` for keys within
+/// sections. Secrets can be kept in files of their own, whose paths are set
+/// by the same key with a `_file` suffix, like `amqp.password_file`.
+pub fn read(path: &str) -> Result<ApplicationConfig, Error> {
   let mut config = Config::new();
-  config.merge(File::with_name("config")).unwrap();
-  let test = config.get("test").unwrap();
-  let host = config.get("amqp.host").unwrap();
-  let queue = config.get("amqp.queue").unwrap();
-  let username = config.get("amqp.username").unwrap();
-  let password = config.get("amqp.password").unwrap();
-  let thread_count: String = config.get("thread_count").unwrap();
-  let nominatim_url: String = config.get("nominatim_url").unwrap();
-
-  ApplicationConfig {
-    test,
-    thread_count: thread_count.parse().unwrap(),
-    nominatim_url,
-    amqp_config: AmqpConfig {
-      host,
-      queue,
-      username,
-      password,
-    },
+  let targets_path = targets_path(path);
+  if let Err(e) = config.merge(File::with_name(path)).and_then(|config| {
+    config.merge(File::with_name(&targets_path.to_string_lossy()).required(false))
+  }) {
+    return Err(Error::new(vec![Problem::new(
+      path,
+      format!("could not be read: {}", e),
+    )]));
+  }
+  override_config(
+    &mut config,
+    Environment::with_prefix(ENV_PREFIX).separator(ENV_SEPARATOR),
+  )?;
+  read_config(&config)
+}
+
+/// Every file that the configuration at `path` may be read from, whether it
+/// exists or not.
+pub fn files(path: &str) -> Vec<PathBuf> {
+  let mut files = Vec::new();
+  for file in [PathBuf::from(path), targets_path(path)].iter() {
+    files.push(file.clone());
+    for extension in EXTENSIONS.iter() {
+      files.push(file.with_extension(extension));
+    }
+  }
+  files
+}
+
+fn targets_path(path: &str) -> PathBuf {
+  Path::new(path).with_file_name("targets")
+}
+
+/// Applies the environment variables and the secret files to the config.
+fn override_config(config: &mut Config, environment: Environment) -> Result<(), Error> {
+  if let Err(e) = config.merge(environment) {
+    return Err(Error::new(vec![Problem::new(
+      ENV_PREFIX,
+      format!("environment variables could not be read: {}", e),
+    )]));
+  }
+  let root = config
+    .clone()
+    .try_into::<HashMap<String, Value>>()
+    .unwrap_or_default();
+  let mut problems = Vec::new();
+  for (key, value) in find_secret_files("", root) {
+    let file_key = format!("{}{}", key, FILE_SUFFIX);
+    let secret = value
+      .into_str()
+      .map_err(|e| e.to_string())
+      .and_then(|path| fs::read_to_string(path).map_err(|e| e.to_string()));
+    match secret {
+      Ok(secret) => {
+        if let Err(e) = config.set(&key, secret.trim_end_matches(&['\r', '\n'][..])) {
+          problems.push(Problem::new(&file_key, format!("could not be set: {}", e)));
+        }
+      }
+      Err(e) => problems.push(Problem::new(&file_key, format!("could not be read: {}", e))),
+    }
+  }
+  if problems.is_empty() {
+    Ok(())
+  } else {
+    Err(Error::new(problems))
+  }
+}
+
+/// Finds the keys whose values are kept in files, along with the paths of
+/// those files. Sections are searched as well, but not arrays of them.
+fn find_secret_files(prefix: &str, table: HashMap<String, Value>) -> Vec<(String, Value)> {
+  let mut secret_files = Vec::new();
+  for (key, value) in table {
+    if key.ends_with(FILE_SUFFIX) {
+      let key = &key[..key.len() - FILE_SUFFIX.len()];
+      secret_files.push((format!("{}{}", prefix, key), value));
+    } else if let Ok(section) = value.into_table() {
+      secret_files.extend(find_secret_files(&format!("{}{}.", prefix, key), section));
+    }
+  }
+  secret_files
+}
+
+fn read_config(config: &Config) -> Result<ApplicationConfig, Error> {
+  let mut problems = Vec::new();
+  let test = optional(config, "test", &mut problems).unwrap_or(false);
+  let sinks = unless_invalid(read_sinks(config), &mut problems);
+  // older configurations still call it thread_count
+  let concurrency = optional(config, "concurrency", &mut problems)
+    .or_else(|| optional(config, "thread_count", &mut problems))
+    .unwrap_or(DEFAULT_CONCURRENCY);
+  if concurrency == 0 {
+    problems.push(Problem::new(
+      "concurrency",
+      "has to be at least 1".to_owned(),
+    ));
+  }
+  let nominatim_url = optional(config, "nominatim_url", &mut problems)
+    .unwrap_or_else(|| DEFAULT_NOMINATIM_URL.to_owned());
+  let store_path =
+    optional(config, "store_path", &mut problems).unwrap_or_else(|| DEFAULT_STORE_PATH.to_owned());
+  let shutdown_timeout = optional(config, "shutdown_timeout", &mut problems)
+    .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_IN_SECONDS);
+  let exit_timeout =
+    optional(config, "exit_timeout", &mut problems).unwrap_or(DEFAULT_EXIT_TIMEOUT_IN_SECONDS);
+  let crawl_timeout = optional(config, "crawl_timeout", &mut problems);
+  if crawl_timeout == Some(0) {
+    problems.push(Problem::new(
+      "crawl_timeout",
+      "has to be at least 1 second".to_owned(),
+    ));
+  }
+  let (schedule, source_schedules) = unless_invalid(read_schedules(config), &mut problems);
+  let defaults = TargetDefaults {
+    request: unless_invalid(read_request(config), &mut problems),
+    // an invalid timeout has already been reported, not for every target
+    crawl_timeout: crawl_timeout.filter(|crawl_timeout| *crawl_timeout > 0),
+    schedule,
+    source_schedules,
+  };
+  let rate_limits = unless_invalid(read_rate_limits(config), &mut problems);
+  let cities = unless_invalid(read_cities(config), &mut problems);
+  let targets = unless_invalid(read_targets(config, &cities, &defaults), &mut problems);
+
+  if problems.is_empty() {
+    Ok(ApplicationConfig {
+      test,
+      concurrency,
+      shutdown_timeout: Duration::from_secs(shutdown_timeout),
+      exit_timeout: Duration::from_secs(exit_timeout),
+      nominatim_url,
+      store_path,
+      request: defaults.request,
+      rate_limits,
+      sinks,
+      cities,
+      targets,
+    })
+  } else {
+    Err(Error::new(problems))
+  }
+}
+
+/// Reads a key that may be left out, but must be valid if it is set.
+fn optional<T: DeserializeOwned>(
+  config: &Config,
+  key: &str,
+  problems: &mut Vec<Problem>,
+) -> Option<T> {
+  match config.get(key) {
+    Ok(value) => Some(value),
+    Err(ConfigError::NotFound(_)) => None,
+    Err(e) => {
+      problems.push(Problem::new(key, format!("could not be read: {}", e)));
+      None
+    }
+  }
+}
+
+/// Takes the problems of a section, so that the other sections can still be
+/// checked as well.
+fn unless_invalid<T: Default>(result: Result<T, Error>, problems: &mut Vec<Problem>) -> T {
+  result.unwrap_or_else(|e| {
+    problems.extend(e.problems);
+    T::default()
+  })
+}
+
+fn read_request(config: &Config) -> Result<RequestSettings, Error> {
+  let request: RequestSettings = match config.get("request") {
+    Ok(request) => request,
+    Err(ConfigError::NotFound(_)) => RequestSettings::default(),
+    Err(e) => {
+      return Err(Error::new(vec![Problem::new(
+        "request",
+        format!("could not be read: {}", e),
+      )]))
+    }
+  };
+  let problems: Vec<Problem> = request
+    .validate()
+    .into_iter()
+    .map(|error| Problem::new("request", error))
+    .collect();
+  if problems.is_empty() {
+    Ok(request)
+  } else {
+    Err(Error::new(problems))
+  }
+}
+
+fn read_schedules(
+  config: &Config,
+) -> Result<(ScheduleSettings, HashMap<String, ScheduleSettings>), Error> {
+  let schedule: ScheduleSettings = match config.get("schedule") {
+    Ok(schedule) => schedule,
+    Err(ConfigError::NotFound(_)) => ScheduleSettings::default(),
+    Err(e) => {
+      return Err(Error::new(vec![Problem::new(
+        "schedule",
+        format!("could not be read: {}", e),
+      )]))
+    }
+  };
+  let source_configs: Vec<SourceConfig> = match config.get("source") {
+    Ok(source_configs) => source_configs,
+    Err(ConfigError::NotFound(_)) => vec![],
+    Err(e) => {
+      return Err(Error::new(vec![Problem::new(
+        "source",
+        format!("could not be read: {}", e),
+      )]))
+    }
+  };
+
+  let mut problems: Vec<Problem> = schedule
+    .schedule()
+    .err()
+    .unwrap_or_default()
+    .into_iter()
+    .map(|error| Problem::new("schedule", error))
+    .collect();
+  let mut source_schedules = HashMap::new();
+  for (index, source_config) in source_configs.into_iter().enumerate() {
+    let key = format!("source #{}", index + 1);
+    let source_schedule = schedule.merge(&source_config.schedule);
+    for error in source_schedule.schedule().err().unwrap_or_default() {
+      problems.push(Problem::new(&key, error));
+    }
+    match source_config.name {
+      Some(name) if CrawlerImpl::from_name(&name).is_some() => {
+        source_schedules.insert(name.to_lowercase(), source_config.schedule);
+      }
+      Some(name) => problems.push(Problem::new(
+        &key,
+        format!(
+          "unknown crawler '{}', expected one of: {}",
+          name,
+          CrawlerImpl::NAMES.join(", ")
+        ),
+      )),
+      None => problems.push(Problem::new(&key, "missing key 'name'".to_owned())),
+    }
+  }
+
+  if problems.is_empty() {
+    Ok((schedule, source_schedules))
+  } else {
+    Err(Error::new(problems))
+  }
+}
+
+fn read_rate_limits(config: &Config) -> Result<RateLimits, Error> {
+  let defaults: RateLimit = match config.get("rate_limit") {
+    Ok(defaults) => defaults,
+    Err(ConfigError::NotFound(_)) => RateLimit::default(),
+    Err(e) => {
+      return Err(Error::new(vec![Problem::new(
+        "rate_limit",
+        format!("could not be read: {}", e),
+      )]))
+    }
+  };
+  let host_configs: Vec<HostConfig> = match config.get("host") {
+    Ok(host_configs) => host_configs,
+    Err(ConfigError::NotFound(_)) => vec![],
+    Err(e) => {
+      return Err(Error::new(vec![Problem::new(
+        "host",
+        format!("could not be read: {}", e),
+      )]))
+    }
+  };
+
+  let mut problems: Vec<Problem> = defaults
+    .validate()
+    .into_iter()
+    .map(|error| Problem::new("rate_limit", error))
+    .collect();
+  let mut rate_limits = RateLimits {
+    defaults,
+    hosts: Default::default(),
+  };
+  for (index, host_config) in host_configs.into_iter().enumerate() {
+    let key = format!("host #{}", index + 1);
+    for error in host_config.limit.validate() {
+      problems.push(Problem::new(&key, error));
+    }
+    match host_config.name {
+      Some(name) => {
+        rate_limits
+          .hosts
+          .insert(name.to_lowercase(), host_config.limit);
+      }
+      None => problems.push(Problem::new(&key, "missing key 'name'".to_owned())),
+    }
+  }
+
+  if problems.is_empty() {
+    Ok(rate_limits)
+  } else {
+    Err(Error::new(problems))
+  }
+}
+
+/// Reads the `[amqp]` section, which is the default sink, along with the
+/// `[[sink]]` entries. Their ids have to be unique, as every sink has an
+/// outbox of its own.
+fn read_sinks(config: &Config) -> Result<Vec<SinkEntry>, Error> {
+  let mut problems = Vec::new();
+  let mut sinks = Vec::new();
+  if let Some(amqp_config) = optional::<AmqpConfig>(config, "amqp", &mut problems) {
+    let missing_keys = amqp_config.missing_keys();
+    let errors = amqp_config.validate();
+    for key in &missing_keys {
+      problems.push(Problem::new(
+        &format!("amqp.{}", key),
+        "missing key".to_owned(),
+      ));
+    }
+    for error in &errors {
+      problems.push(Problem::new("amqp", error.to_owned()));
+    }
+    if missing_keys.is_empty() && errors.is_empty() {
+      sinks.push(SinkEntry {
+        id: optional(config, "amqp.id", &mut problems),
+        config: SinkConfig::Amqp(Box::new(amqp_config)),
+      });
+    }
+  }
+
+  let sink_values: Vec<Value> = optional(config, "sink", &mut problems).unwrap_or_default();
+  for (index, value) in sink_values.into_iter().enumerate() {
+    let key = format!("sink #{}", index + 1);
+    match value.try_into::<SinkEntry>() {
+      Ok(sink) => {
+        let errors = sink.config.validate();
+        if errors.is_empty() {
+          sinks.push(sink);
+        }
+        for error in errors {
+          problems.push(Problem::new(&key, error));
+        }
+      }
+      Err(e) => problems.push(Problem::new(&key, format!("could not be read: {}", e))),
+    }
+  }
+
+  if sinks.is_empty() && problems.is_empty() {
+    problems.push(Problem::new(
+      "sink",
+      "no sinks have been configured, add an [amqp] section or at least one [[sink]]".to_owned(),
+    ));
+  }
+  let mut ids = HashSet::new();
+  for sink in sinks.iter() {
+    if !ids.insert(sink.id()) {
+      problems.push(Problem::new(
+        "sink",
+        format!(
+          "more than one sink has the id '{}', give each of them an 'id' of its own",
+          sink.id()
+        ),
+      ));
+    }
+  }
+  if problems.is_empty() {
+    Ok(sinks)
+  } else {
+    Err(Error::new(problems))
+  }
+}
+
+fn read_cities(config: &Config) -> Result<Cities, Error> {
+  let mut cities = Cities::default();
+  let city_infos: Vec<CityInfo> = match config.get("city") {
+    Ok(city_infos) => city_infos,
+    Err(ConfigError::NotFound(_)) => vec![],
+    Err(e) => {
+      return Err(Error::new(vec![Problem::new(
+        "city",
+        format!("could not be read: {}", e),
+      )]))
+    }
+  };
+  for city_info in city_infos {
+    cities.add(city_info);
+  }
+  Ok(cities)
+}
+
+fn read_targets(
+  config: &Config,
+  cities: &Cities,
+  defaults: &TargetDefaults,
+) -> Result<Vec<CrawlConfig>, Error> {
+  let target_configs: Vec<TargetConfig> = match config.get("target") {
+    Ok(target_configs) => target_configs,
+    Err(ConfigError::NotFound(_)) => vec![],
+    Err(e) => {
+      return Err(Error::new(vec![Problem::new(
+        "target",
+        format!("could not be read: {}", e),
+      )]))
+    }
+  };
+  if target_configs.is_empty() {
+    return Err(Error::new(vec![Problem::new(
+      "target",
+      "no crawl targets have been configured, add at least one [[target]]".to_owned(),
+    )]));
+  }
+
+  let mut targets = Vec::new();
+  let mut problems = Vec::new();
+  for (index, target_config) in target_configs.iter().enumerate() {
+    match validate_target(target_config, cities, defaults) {
+      Ok(target) => targets.push(target),
+      Err(target_errors) => {
+        let key = format!("target #{}", index + 1);
+        for error in target_errors {
+          problems.push(Problem::new(&key, error));
+        }
+      }
+    }
+  }
+
+  if problems.is_empty() {
+    Ok(targets)
+  } else {
+    Err(Error::new(problems))
+  }
+}
+
+fn validate_target(
+  target_config: &TargetConfig,
+  cities: &Cities,
+  defaults: &TargetDefaults,
+) -> Result<CrawlConfig, Vec<String>> {
+  let mut errors = Vec::new();
+
+  let crawler = match &target_config.crawler {
+    Some(name) => {
+      let crawler = CrawlerImpl::from_name(name);
+      if crawler.is_none() {
+        errors.push(format!(
+          "unknown crawler '{}', expected one of: {}",
+          name,
+          CrawlerImpl::NAMES.join(", ")
+        ));
+      }
+      crawler
+    }
+    None => {
+      errors.push("missing key 'crawler'".to_owned());
+      None
+    }
+  };
+
+  let city = match &target_config.city {
+    Some(name) => {
+      let city = cities.find(name).map(|info| info.city());
+      if city.is_none() {
+        errors.push(format!(
+          "unknown city '{}', expected one of: {}",
+          name,
+          cities.slugs().join(", ")
+        ));
+      }
+      city
+    }
+    None => {
+      errors.push("missing key 'city'".to_owned());
+      None
+    }
+  };
+
+  let encoding = match &target_config.encoding {
+    Some(name) => {
+      let encoding = Encoding::from_name(name);
+      if encoding.is_none() {
+        errors.push(format!(
+          "unknown encoding '{}', expected a label like utf-8, latin1 or iso-8859-15",
+          name
+        ));
+      }
+      encoding
+    }
+    None => None,
+  };
+
+  if target_config.host.is_none() {
+    errors.push("missing key 'host'".to_owned());
+  }
+  if target_config.path.is_none() {
+    errors.push("missing key 'path'".to_owned());
+  }
+  let max_pages = target_config.max_pages.unwrap_or(1);
+  if max_pages == 0 {
+    errors.push("'max_pages' has to be at least 1".to_owned());
+  }
+  if target_config.stop_after_known == Some(0) {
+    errors.push("'stop_after_known' has to be at least 1".to_owned());
+  }
+  let request = defaults.request.merge(&target_config.request);
+  errors.extend(target_config.request.validate());
+  let crawl_timeout = target_config
+    .crawl_timeout
+    .or(defaults.crawl_timeout)
+    .unwrap_or(DEFAULT_CRAWL_TIMEOUT_IN_SECONDS);
+  if crawl_timeout == 0 {
+    errors.push("'crawl_timeout' has to be at least 1 second".to_owned());
+  }
+  let source_schedule = target_config
+    .crawler
+    .as_ref()
+    .and_then(|name| defaults.source_schedules.get(&name.to_lowercase()))
+    .map_or_else(
+      || defaults.schedule.clone(),
+      |source_schedule| defaults.schedule.merge(source_schedule),
+    );
+  let schedule = match source_schedule.merge(&target_config.schedule).schedule() {
+    Ok(schedule) => Some(schedule),
+    Err(schedule_errors) => {
+      errors.extend(schedule_errors);
+      None
+    }
+  };
+
+  match (
+    crawler,
+    city,
+    schedule,
+    &target_config.host,
+    &target_config.path,
+  ) {
+    (Some(crawler), Some(city), Some(schedule), Some(host), Some(path)) if errors.is_empty() => {
+      Ok(CrawlConfig {
+        host: host.to_owned(),
+        path: path.to_owned(),
+        city,
+        encoding,
+        crawler,
+        max_pages,
+        stop_after_known: target_config.stop_after_known,
+        details: target_config.details.unwrap_or(false),
+        request,
+        crawl_timeout: Duration::from_secs(crawl_timeout),
+        schedule,
+      })
+    }
+    _ => Err(errors),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    override_config, read_cities, read_config, read_rate_limits, read_request, read_schedules,
+    read_sinks, read_targets, TargetDefaults,
+  };
+  use crate::crawlers::CrawlerImpl;
+  use crate::models::{Cities, City};
+  use crate::sinks::{AmqpConfig, SinkConfig, SinkEntry};
+  use config::{Config, Environment, File, FileFormat};
+  use std::env;
+  use std::fs;
+  use std::time::Duration;
+
+  const TARGET: &str = r#"
+      [[target]]
+      crawler = "wggesucht"
+      city = "Munich"
+      host = "www.wg-gesucht.de"
+      path = "/"
+      "#;
+
+  fn config(toml: &str) -> Config {
+    let mut config = Config::new();
+    config
+      .merge(File::from_str(toml, FileFormat::Toml))
+      .unwrap();
+    config
+  }
+
+  #[test]
+  fn reads_targets() {
+    let cities = Cities::default();
+    let targets = read_targets(
+      &config(
+        r#"
+      [[target]]
+      crawler = "immoscout"
+      city = "Munich"
+      host = "www.immobilienscout24.de"
+      path = "/Suche/S-2/P-1/Wohnung-Miete/Bayern/Muenchen"
+
+      [[target]]
+      crawler = "sueddeutsche"
+      city = "wuerzburg"
+      host = "immobilienmarkt.sueddeutsche.de"
+      path = "/Angebote/mieten/Wohnung-Stadt_Wuerzburg"
+      encoding = "latin1"
+      "#,
+      ),
+      &cities,
+      &TargetDefaults::default(),
+    )
+    .unwrap();
+
+    assert_eq!(targets.len(), 2);
+    assert_eq!(targets[0].crawler, CrawlerImpl::ImmoScout);
+    assert_eq!(targets[0].city, City::new("Munich"));
+    assert_eq!(targets[1].crawler, CrawlerImpl::Sueddeutsche);
+    assert_eq!(targets[1].city, City::new("Wuerzburg"));
+  }
+
+  #[test]
+  fn reports_every_invalid_target() {
+    let cities = Cities::default();
+    let error = read_targets(
+      &config(
+        r#"
+      [[target]]
+      crawler = "immoscot"
+      city = "Munich"
+      host = "www.immobilienscout24.de"
+      path = "/"
+
+      [[target]]
+      crawler = "immowelt"
+      city = "Berlin"
+      path = "/"
+
+      [[target]]
+      crawler = "wggesucht"
+      city = "Munich"
+      host = "www.wg-gesucht.de"
+      path = "/"
+      max_pages = 0
+      "#,
+      ),
+      &cities,
+      &TargetDefaults::default(),
+    )
+    .err()
+    .unwrap();
+
+    assert_eq!(
+      error.message.lines().collect::<Vec<_>>(),
+      vec![
+        "target #1: unknown crawler 'immoscot', expected one of: immoscout, immowelt, sueddeutsche, wggesucht, wohnungsboerse",
+        "target #2: unknown city 'Berlin', expected one of: Munich, Wuerzburg, Augsburg, Kempten",
+        "target #2: missing key 'host'",
+        "target #3: 'max_pages' has to be at least 1",
+      ]
+    );
+  }
+
+  #[test]
+  fn reports_every_missing_or_invalid_key() {
+    let error = read_config(&config(&format!(
+      r#"
+      concurrency = "many"
+      crawl_timeout = 0
+
+      [amqp]
+      username = "flatcrawl"
+      {}"#,
+      TARGET
+    )))
+    .err()
+    .unwrap();
+
+    assert_eq!(
+      error
+        .problems
+        .iter()
+        .map(|problem| problem.key.as_str())
+        .collect::<Vec<_>>(),
+      vec!["amqp.host", "amqp.password", "concurrency", "crawl_timeout"]
+    );
+    assert_eq!(error.message.lines().next(), Some("amqp.host: missing key"));
+  }
+
+  #[test]
+  fn overrides_keys_by_environment_and_files() {
+    let password_path = env::temp_dir().join("flatcrawl-configuration-test-password");
+    fs::write(&password_path, "secret\n").unwrap();
+    env::set_var("FLATCRAWL_TEST_AMQP__HOST", "broker");
+    env::set_var("FLATCRAWL_TEST_CONCURRENCY", "8");
+    let mut config = config(&format!(
+      r#"
+      [amqp]
+      host = "localhost"
+      username = "flatcrawl"
+      password_file = "{}"
+      {}"#,
+      password_path.display(),
+      TARGET
+    ));
+    override_config(
+      &mut config,
+      Environment::with_prefix("FLATCRAWL_TEST").separator("__"),
+    )
+    .unwrap();
+    let app_config = read_config(&config).unwrap();
+    fs::remove_file(&password_path).unwrap();
+
+    assert_eq!(
+      app_config.sinks,
+      vec![SinkEntry {
+        id: None,
+        config: SinkConfig::Amqp(Box::new(AmqpConfig {
+          host: "broker".to_owned(),
+          username: "flatcrawl".to_owned(),
+          password: "secret".to_owned(),
+          ..Default::default()
+        }))
+      }]
+    );
+    assert_eq!(app_config.concurrency, 8);
+    assert_eq!(app_config.store_path, "flatcrawl.db");
+    assert!(!app_config.test);
+  }
+
+  #[test]
+  fn reads_request_settings() {
+    let config = config(
+      r#"
+      [request]
+      user_agent = "flatcrawl"
+      timeout = 20
+      accept_language = "de-DE"
+      headers = { "X-Requested-With" = "flatcrawl" }
+
+      [[target]]
+      crawler = "wggesucht"
+      city = "Munich"
+      host = "www.wg-gesucht.de"
+      path = "/"
+      scheme = "http"
+      timeout = 60
+
+      [[target]]
+      crawler = "immowelt"
+      city = "Munich"
+      host = "www.immowelt.de"
+      path = "/"
+      "#,
+    );
+    let defaults = TargetDefaults {
+      request: read_request(&config).unwrap(),
+      crawl_timeout: Some(120),
+      ..Default::default()
+    };
+    let targets = read_targets(&config, &Cities::default(), &defaults).unwrap();
+
+    assert_eq!(targets[0].request.scheme(), "http");
+    assert_eq!(targets[0].request.timeout, Some(60));
+    assert_eq!(targets[0].request.user_agent.as_deref(), Some("flatcrawl"));
+    assert_eq!(targets[1].request.scheme(), "https");
+    assert_eq!(targets[1].request.timeout, Some(20));
+    assert_eq!(targets[1].request.headers["x-requested-with"], "flatcrawl");
+    assert_eq!(targets[1].crawl_timeout, Duration::from_secs(120));
+  }
+
+  #[test]
+  fn reads_rate_limits() {
+    let invalid = config(
+      r#"
+      [rate_limit]
+      min_delay = 500
+
+      [[host]]
+      name = "www.immobilienscout24.de"
+      min_delay = 3000
+      max_concurrent = 1
+
+      [[host]]
+      max_concurrent = 0
+      "#,
+    );
+    let error = read_rate_limits(&invalid).err().unwrap();
+
+    assert_eq!(
+      error.message.lines().collect::<Vec<_>>(),
+      vec![
+        "host #2: 'max_concurrent' has to be at least 1",
+        "host #2: missing key 'name'",
+      ]
+    );
+
+    let valid = config(
+      r#"
+      [rate_limit]
+      min_delay = 500
+
+      [[host]]
+      name = "www.immobilienscout24.de"
+      min_delay = 3000
+      max_concurrent = 1
+      "#,
+    );
+    let rate_limits = read_rate_limits(&valid).unwrap();
+    let immoscout = rate_limits.get("www.immobilienscout24.de");
+    let immowelt = rate_limits.get("www.immowelt.de");
+
+    assert_eq!(immoscout.min_delay, Some(3000));
+    assert_eq!(immoscout.max_concurrent(), 1);
+    assert_eq!(immowelt.min_delay, Some(500));
+    assert_eq!(immowelt.max_concurrent(), 2);
+  }
+
+  #[test]
+  fn reads_schedules() {
+    let config = config(
+      r#"
+      [schedule]
+      interval = 600
+      quiet_hours = ["* 0-5 * * *"]
+
+      [[source]]
+      name = "immoscout"
+      interval = 120
+      jitter = 10
+
+      [[target]]
+      crawler = "immoscout"
+      city = "Munich"
+      host = "www.immobilienscout24.de"
+      path = "/munich"
+
+      [[target]]
+      crawler = "immoscout"
+      city = "Kempten"
+      host = "www.immobilienscout24.de"
+      path = "/kempten"
+      interval = 3600
+      quiet_hours = []
+
+      [[target]]
+      crawler = "immowelt"
+      city = "Munich"
+      host = "www.immowelt.de"
+      path = "/"
+      "#,
+    );
+    let (schedule, source_schedules) = read_schedules(&config).unwrap();
+    let defaults = TargetDefaults {
+      schedule,
+      source_schedules,
+      ..Default::default()
+    };
+    let targets = read_targets(&config, &Cities::default(), &defaults).unwrap();
+
+    assert_eq!(targets[0].schedule.interval, Duration::from_secs(120));
+    assert_eq!(targets[0].schedule.jitter, Duration::from_secs(10));
+    assert_eq!(targets[0].schedule.quiet_hours.len(), 1);
+    assert_eq!(targets[1].schedule.interval, Duration::from_secs(3600));
+    assert!(targets[1].schedule.quiet_hours.is_empty());
+    assert_eq!(targets[2].schedule.interval, Duration::from_secs(600));
+  }
+
+  #[test]
+  fn reports_invalid_schedules() {
+    let error = read_schedules(&config(
+      r#"
+      [[source]]
+      name = "immoscot"
+
+      [[source]]
+      name = "immowelt"
+      quiet_hours = ["at night"]
+      "#,
+    ))
+    .err()
+    .unwrap();
+
+    assert_eq!(
+      error.message.lines().collect::<Vec<_>>(),
+      vec![
+        "source #1: unknown crawler 'immoscot', expected one of: immoscout, immowelt, sueddeutsche, wggesucht, wohnungsboerse",
+        "source #2: cron expression 'at night' needs 5 fields, but has 2",
+      ]
+    );
+  }
+
+  #[test]
+  fn reads_sinks() {
+    let sinks = read_sinks(&config(
+      r#"
+      [amqp]
+      host = "localhost"
+      username = "flatcrawl"
+      password = "secret"
+
+      [[sink]]
+      type = "stdout"
+      "#,
+    ))
+    .unwrap();
+    let error = read_sinks(&config(
+      r#"
+      [[sink]]
+      type = "pigeon"
+
+      [[sink]]
+      type = "jsonl"
+      "#,
+    ))
+    .err()
+    .unwrap();
+
+    assert_eq!(sinks.len(), 2);
+    assert_eq!(sinks[0].id(), "amqp");
+    assert_eq!(sinks[1].config, SinkConfig::Stdout);
+    assert_eq!(
+      error
+        .problems
+        .iter()
+        .map(|problem| problem.key.as_str())
+        .collect::<Vec<_>>(),
+      vec!["sink #1", "sink #2"]
+    );
+    assert_eq!(
+      read_sinks(&config("test = true")).err().unwrap().problems[0].key,
+      "sink"
+    );
+  }
+
+  #[test]
+  fn requires_distinct_sink_ids() {
+    let error = read_sinks(&config(
+      r#"
+      [[sink]]
+      type = "stdout"
+
+      [[sink]]
+      type = "stdout"
+      "#,
+    ))
+    .err()
+    .unwrap();
+    let sinks = read_sinks(&config(
+      r#"
+      [amqp]
+      id = "broker"
+      host = "localhost"
+      username = "flatcrawl"
+      password = "secret"
+
+      [[sink]]
+      type = "stdout"
+
+      [[sink]]
+      type = "stdout"
+      id = "console"
+      "#,
+    ))
+    .unwrap();
+
+    assert_eq!(
+      error.problems[0].reason,
+      "more than one sink has the id 'stdout', give each of them an 'id' of its own"
+    );
+    assert_eq!(
+      sinks.iter().map(|sink| sink.id()).collect::<Vec<_>>(),
+      vec!["broker", "stdout", "console"]
+    );
+  }
+
+  #[test]
+  fn requires_targets() {
+    assert!(read_targets(
+      &config("test = true"),
+      &Cities::default(),
+      &TargetDefaults::default()
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn reads_additional_cities() {
+    let config = config(
+      r#"
+      [[city]]
+      slug = "Berlin"
+      name = "Berlin"
+      country = "DE"
+      bounding_box = { min_lat = 52.3383, max_lat = 52.6755, min_lon = 13.0884, max_lon = 13.7611 }
+
+      [[target]]
+      crawler = "immowelt"
+      city = "berlin"
+      host = "www.immowelt.de"
+      path = "/liste/berlin/wohnungen/mieten"
+      "#,
+    );
+    let cities = read_cities(&config).unwrap();
+    let targets = read_targets(&config, &cities, &TargetDefaults::default()).unwrap();
+
+    assert!(cities.find("Munich").is_some());
+    assert_eq!(targets[0].city, City::new("Berlin"));
   }
 }
@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// how often a sleeping thread looks whether it has been cancelled
+const POLL_INTERVAL_IN_MILLIS: u64 = 100;
+
+/// Tells a running crawl to stop, it will do so before its next request.
+/// Clones share their state, so a crawl can be cancelled from anywhere else.
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation {
+  cancelled: Arc<AtomicBool>,
+  parent: Option<Box<Cancellation>>,
+}
+
+impl Cancellation {
+  pub fn new() -> Cancellation {
+    Default::default()
+  }
+
+  /// Creates a cancellation that can be triggered on its own, but is also
+  /// triggered along with this one.
+  pub fn child(&self) -> Cancellation {
+    Cancellation {
+      cancelled: Default::default(),
+      parent: Some(Box::new(self.clone())),
+    }
+  }
+
+  /// The flag of this cancellation itself, e.g. for signal handlers to set.
+  pub(crate) fn flag(&self) -> Arc<AtomicBool> {
+    self.cancelled.clone()
+  }
+
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::SeqCst);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::SeqCst)
+      || self
+        .parent
+        .as_ref()
+        .is_some_and(|parent| parent.is_cancelled())
+  }
+
+  /// Sleeps for the given duration, but wakes up early when cancelled.
+  /// Returns whether it has been.
+  pub fn sleep(&self, duration: Duration) -> bool {
+    let end = Instant::now() + duration;
+    while !self.is_cancelled() {
+      let now = Instant::now();
+      if now >= end {
+        return false;
+      }
+      thread::sleep((end - now).min(Duration::from_millis(POLL_INTERVAL_IN_MILLIS)));
+    }
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Cancellation;
+
+  #[test]
+  fn cancels_children() {
+    let parent = Cancellation::new();
+    let first = parent.child();
+    let second = parent.child();
+
+    first.cancel();
+    assert!(first.is_cancelled());
+    assert!(!second.is_cancelled());
+    assert!(!parent.is_cancelled());
+
+    parent.cancel();
+    assert!(second.clone().is_cancelled());
+  }
+}
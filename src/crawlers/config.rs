@@ -1,11 +1,30 @@
 use super::CrawlerImpl;
+use crate::http::RequestSettings;
 use crate::models::City;
 use crate::models::Encoding;
+use crate::schedule::Schedule;
+use std::time::Duration;
 
+#[derive(Clone, Debug)]
 pub struct Config {
-  pub host: &'static str,
-  pub path: &'static str,
+  pub host: String,
+  pub path: String,
   pub city: City,
-  pub encoding: Encoding,
+  /// used when the pages do not tell how they are encoded
+  pub encoding: Option<Encoding>,
   pub crawler: CrawlerImpl,
+  pub max_pages: u32,
+  pub stop_after_known: Option<u32>,
+  pub details: bool,
+  pub request: RequestSettings,
+  /// how long crawling all pages of the target may take
+  pub crawl_timeout: Duration,
+  pub schedule: Schedule,
+}
+
+impl Config {
+  /// Identifies the target, no two targets crawl the same page.
+  pub fn id(&self) -> String {
+    format!("{}{}", self.host, self.path)
+  }
 }
@@ -16,19 +16,49 @@ pub struct Error {
 
 impl From<std::num::ParseFloatError> for Error {
   fn from(_err: std::num::ParseFloatError) -> Error {
-    return Error {
+    Error {
       message: "Could not parse float!".to_owned(),
-    };
+    }
   }
 }
 
+/// Describes how the crawler gets from one page of results to the next.
+pub enum Pagination {
+  /// All results are listed on a single page.
+  None,
+  /// The next page is linked by the element matching the given selector.
+  NextLink(&'static str),
+  /// Pages are addressed by their number, given as the named query parameter.
+  PageParameter(&'static str),
+}
+
 pub trait Crawler: Send + Sync {
   fn name(&self) -> &'static str;
 
   fn selector(&self) -> &'static str;
 
+  fn pagination(&self) -> Pagination {
+    Pagination::None
+  }
+
   fn transform_result(&self, result: NodeDataRef<ElementData>) -> Result<FlatData, Error>;
 
+  /// The page that contains all details of the flat, which defaults to the
+  /// link found in the result list. It may be given relative to the page the
+  /// flat has been listed on and will become the canonical url of the flat.
+  fn detail_url(&self, data: &FlatData) -> Option<String> {
+    data.url.clone()
+  }
+
+  /// Enriches the flat with the information found on its detail page.
+  fn transform_detail(
+    &self,
+    _detail: NodeDataRef<ElementData>,
+    data: FlatData,
+  ) -> Result<FlatData, Error> {
+    Ok(data)
+  }
+
   fn get_attr(
     element: &NodeDataRef<ElementData>,
     select_opt: Option<&'static str>,
@@ -70,6 +100,115 @@ pub trait Crawler: Send + Sync {
     }
   }
 
+  /// Like `get_text`, but trimmed and `None` when there is no or only empty text.
+  fn find_text(result: &NodeDataRef<ElementData>, selector: &'static str) -> Option<String>
+  where
+    Self: Sized,
+  {
+    Self::get_text(result, selector)
+      .ok()
+      .map(|text| text.trim().to_owned())
+      .filter(|text| !text.is_empty())
+  }
+
+  fn find_texts(result: &NodeDataRef<ElementData>, selector: &'static str) -> Vec<String>
+  where
+    Self: Sized,
+  {
+    match result.as_node().select(selector) {
+      Ok(elements) => elements
+        .map(|element| element.text_contents().trim().to_owned())
+        .filter(|text| !text.is_empty())
+        .collect(),
+      Err(()) => vec![],
+    }
+  }
+
+  fn find_number(result: &NodeDataRef<ElementData>, selector: &'static str) -> Option<f32>
+  where
+    Self: Sized,
+  {
+    Self::find_text(result, selector).and_then(|text| Self::parse_number(text).ok())
+  }
+
+  /// Collects the attribute of all elements matching the selector.
+  fn find_attrs(
+    result: &NodeDataRef<ElementData>,
+    selector: &'static str,
+    name: &'static str,
+  ) -> Vec<String>
+  where
+    Self: Sized,
+  {
+    match result.as_node().select(selector) {
+      Ok(elements) => elements
+        .filter_map(|element| {
+          element
+            .attributes
+            .borrow()
+            .get(name)
+            .map(|val| val.to_owned())
+        })
+        .collect(),
+      Err(()) => vec![],
+    }
+  }
+
+  /// Whether any of the texts mentions the keyword, `None` if it can't be told.
+  fn mentions(texts: &[String], keyword: &str) -> Option<bool>
+  where
+    Self: Sized,
+  {
+    if texts
+      .iter()
+      .any(|text| text.to_lowercase().contains(keyword))
+    {
+      Some(true)
+    } else {
+      None
+    }
+  }
+
+  /// Tells from a text like a title whether the flat comes with furniture.
+  fn parse_furnished(text: &str) -> Option<bool>
+  where
+    Self: Sized,
+  {
+    let lowercase_text = text.to_lowercase();
+    if lowercase_text.contains("unmöbliert") {
+      Some(false)
+    } else if lowercase_text.contains("möbliert") {
+      Some(true)
+    } else {
+      None
+    }
+  }
+
+  /// Parses floors like "3. OG", "3 von 5" or "EG", the ground floor being 0.
+  fn parse_floor(floor_as_str: &str) -> Option<i32>
+  where
+    Self: Sized,
+  {
+    let lowercase_floor = floor_as_str.trim().to_lowercase();
+    if lowercase_floor.starts_with("eg") || lowercase_floor.starts_with("erdgeschoss") {
+      return Some(0);
+    }
+    if lowercase_floor.starts_with("ug") || lowercase_floor.starts_with("souterrain") {
+      return Some(-1);
+    }
+    let start = lowercase_floor.find(|c: char| c.is_ascii_digit())?;
+    let digits: String = lowercase_floor[start..]
+      .chars()
+      .take_while(|c| c.is_ascii_digit())
+      .collect();
+    let floor: i32 = digits.parse().ok()?;
+    if lowercase_floor[..start].ends_with('-') {
+      Some(-floor)
+    } else {
+      Some(floor)
+    }
+  }
+
   fn parse_number(rent_as_str: String) -> Result<f32, Error>
   where
     Self: Sized,
@@ -78,7 +217,7 @@ pub trait Crawler: Send + Sync {
     match rent_regex
       .captures_iter(rent_as_str.as_str())
       .next()
-      .and_then(|capture| Some(capture[0].replace(".", "").replace(",", ".")))
+      .map(|capture| capture[0].replace(".", "").replace(",", "."))
     {
       Some(rent) => Ok(rent.parse()?),
       None => Err(Error {
@@ -91,3 +230,32 @@ pub trait Crawler: Send + Sync {
     println!("{}: {}", self.name(), message);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::Crawler;
+  use crate::crawlers::ImmoScout;
+
+  #[test]
+  fn parse_floor() {
+    assert_eq!(ImmoScout::parse_floor("3 von 5"), Some(3));
+    assert_eq!(ImmoScout::parse_floor("2. OG"), Some(2));
+    assert_eq!(ImmoScout::parse_floor("EG"), Some(0));
+    assert_eq!(ImmoScout::parse_floor("Souterrain"), Some(-1));
+    assert_eq!(ImmoScout::parse_floor("Dachgeschoss"), None);
+    assert_eq!(ImmoScout::parse_floor("-2"), Some(-2));
+  }
+
+  #[test]
+  fn parse_furnished() {
+    assert_eq!(
+      ImmoScout::parse_furnished("Schöne möblierte Wohnung"),
+      Some(true)
+    );
+    assert_eq!(
+      ImmoScout::parse_furnished("2 Zimmer, unmöbliert"),
+      Some(false)
+    );
+    assert_eq!(ImmoScout::parse_furnished("2 Zimmer mit Balkon"), None);
+  }
+}
@@ -1,15 +1,15 @@
-extern crate encoding_rs;
 extern crate kuchiki;
 extern crate regex;
 extern crate reqwest;
 extern crate std;
+extern crate url;
 
-use crate::crawlers::{Config, Crawler, Error as CrawlingError};
+use crate::crawlers::{Cancellation, Config, Crawler, Error as CrawlingError, Pagination};
+use crate::http::{Error as HttpError, Http, Page};
 use crate::models::{Encoding, Flat};
-use kuchiki::iter::*;
 use kuchiki::traits::*;
-use reqwest::Response;
-use std::error::Error as StdErr;
+use kuchiki::NodeRef;
+use url::Url;
 
 #[derive(Debug)]
 pub struct Error {
@@ -18,35 +18,177 @@ pub struct Error {
 
 impl From<CrawlingError> for Error {
   fn from(err: CrawlingError) -> Error {
-    return Error {
+    Error {
       message: format!("Crawler Error: {}", err.message),
-    };
+    }
   }
 }
 
 impl From<std::io::Error> for Error {
   fn from(_err: std::io::Error) -> Error {
-    return Error {
+    Error {
       message: "IO Error".to_owned(),
-    };
+    }
   }
 }
 
 impl From<reqwest::Error> for Error {
-  fn from(_err: reqwest::Error) -> Error {
-    let mut message = String::from("Request Error: ");
-    message.push_str(_err.description());
-    return Error { message };
+  fn from(err: reqwest::Error) -> Error {
+    Error {
+      message: format!("Request Error: {}", err),
+    }
+  }
+}
+
+impl From<HttpError> for Error {
+  fn from(err: HttpError) -> Error {
+    Error {
+      message: err.message,
+    }
+  }
+}
+
+impl From<url::ParseError> for Error {
+  fn from(err: url::ParseError) -> Error {
+    Error {
+      message: format!("Invalid URL: {}", err),
+    }
+  }
+}
+
+/// Query parameters that only serve tracking purposes and will be removed
+/// from the urls of the flats. Parameters starting with `utm_` are removed too.
+const TRACKING_PARAMETERS: [&str; 7] = [
+  "fbclid",
+  "gclid",
+  "navigationServiceUrl",
+  "pagerReporting",
+  "ref",
+  "referrer",
+  "searchId",
+];
+
+/// What a crawl of a single target came up with.
+#[derive(Debug, Default)]
+pub struct CrawlResult {
+  pub flats: Vec<Flat>,
+  /// number of pages that have been crawled
+  pub pages: u32,
+  /// number of requests that have been sent, including retries
+  pub attempts: u32,
+}
+
+/// Crawls the pages of the given target and merges all of their flats.
+///
+/// Pages are followed as long as the crawler can find a next one, the page
+/// limit of the target has not been reached yet and, if configured, there
+/// have not been too many consecutive flats that `is_known` already knows.
+/// Once `cancellation` has been triggered, no further requests will be sent.
+pub fn execute(
+  http: &Http,
+  config: &Config,
+  crawler: &dyn Crawler,
+  is_known: &dyn Fn(&Flat) -> bool,
+  cancellation: &Cancellation,
+) -> Result<CrawlResult, Error> {
+  let first_url = Url::parse(&format!(
+    "{}://{}{}",
+    config.request.scheme(),
+    config.host,
+    config.path
+  ))?;
+  let mut result = CrawlResult::default();
+  let mut url_opt = Some(first_url.clone());
+  let mut page = 1;
+  let mut consecutive_known = 0;
+  while let Some(url) = url_opt {
+    if cancellation.is_cancelled() {
+      return Err(Error {
+        message: format!("crawl has been cancelled at page {}", page),
+      });
+    }
+    let document = match get_document(
+      http,
+      &url,
+      config,
+      crawler,
+      cancellation,
+      &mut result.attempts,
+    ) {
+      Ok(document) => document,
+      Err(e) if page > 1 => {
+        crawler.log(format!("stopping at page {}, because: {}", page, e.message));
+        break;
+      }
+      Err(e) => return Err(e),
+    };
+    result.pages += 1;
+    let flats = transform_results(&document, &url, config, crawler)?;
+    let found_flats = !flats.is_empty();
+    for flat in flats {
+      let known = is_known(&flat);
+      if known {
+        consecutive_known += 1;
+      } else {
+        consecutive_known = 0;
+      }
+      if config.details && !known && !cancellation.is_cancelled() {
+        result.flats.push(fetch_details(
+          http,
+          flat,
+          config,
+          crawler,
+          cancellation,
+          &mut result.attempts,
+        ));
+      } else {
+        result.flats.push(flat);
+      }
+    }
+
+    let reached_known = match config.stop_after_known {
+      Some(limit) if consecutive_known >= limit => {
+        crawler.log(format!(
+          "reached {} known flats in a row on page {}.",
+          consecutive_known, page
+        ));
+        true
+      }
+      _ => false,
+    };
+
+    url_opt = if found_flats && !reached_known && page < config.max_pages {
+      next_page_url(&document, &first_url, &url, page, crawler)
+    } else {
+      None
+    };
+    page += 1;
   }
+  Ok(result)
 }
 
-pub fn execute(config: &Config, crawler: &Box<dyn Crawler>) -> Result<Vec<Flat>, Error> {
-  let results = get_results(config, crawler)?;
+fn transform_results(
+  document: &NodeRef,
+  page_url: &Url,
+  config: &Config,
+  crawler: &dyn Crawler,
+) -> Result<Vec<Flat>, Error> {
+  let results = match document.select(crawler.selector()) {
+    Ok(x) => x,
+    Err(()) => {
+      return Err(Error {
+        message: "Main selector did not match.".to_owned(),
+      })
+    }
+  };
   let mut successful: Vec<Flat> = Vec::new();
   let flat_results: Vec<Result<Flat, Error>> = results
     .map(|result| {
       let flat = Flat::new(crawler.name().to_owned(), config.city.clone());
-      let data = crawler.transform_result(result)?;
+      let mut data = crawler.transform_result(result)?;
+      data.url = crawler
+        .detail_url(&data)
+        .and_then(|url| canonical_url(page_url, &url));
       Ok(flat.fill(&data))
     })
     .collect();
@@ -63,39 +205,190 @@ pub fn execute(config: &Config, crawler: &Box<dyn Crawler>) -> Result<Vec<Flat>,
   Ok(successful)
 }
 
-fn decode_response(response: &mut Response, encoding: &Encoding) -> Result<String, Error> {
-  let mut buf: Vec<u8> = vec![];
-  response.copy_to(&mut buf)?;
-  let (encoded_string, _, _) = match encoding {
-    Encoding::Latin1 => encoding_rs::ISO_8859_2.decode(&buf),
-    Encoding::Utf8 => encoding_rs::UTF_8.decode(&buf),
-  };
-  Ok(encoded_string.into_owned())
+/// Resolves the url of a flat and strips it from anything used for tracking.
+fn canonical_url(page_url: &Url, url: &str) -> Option<String> {
+  let mut canonical = page_url.join(url.trim()).ok()?;
+  let pairs: Vec<(String, String)> = canonical
+    .query_pairs()
+    .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMETERS.contains(&key.as_ref()))
+    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+    .collect();
+  if pairs.is_empty() {
+    canonical.set_query(None);
+  } else {
+    canonical.query_pairs_mut().clear().extend_pairs(pairs);
+  }
+  canonical.set_fragment(None);
+  Some(canonical.to_string())
 }
 
-fn get_results(
+/// Enriches the flat with its detail page, keeps it as it is if that fails.
+fn fetch_details(
+  http: &Http,
+  flat: Flat,
   config: &Config,
-  crawler: &Box<dyn Crawler>,
-) -> Result<Select<Elements<Descendants>>, Error> {
-  let mut url = "http://".to_owned();
-  url.push_str(&config.host);
-  url.push_str(&config.path);
+  crawler: &dyn Crawler,
+  cancellation: &Cancellation,
+  attempts: &mut u32,
+) -> Flat {
+  let (data, detail_url) = match &flat.data {
+    Some(data) => match &data.url {
+      Some(detail_url) => (data.clone(), detail_url.to_owned()),
+      None => return flat,
+    },
+    None => return flat,
+  };
+  let detail_result = Url::parse(&detail_url)
+    .map_err(Error::from)
+    .and_then(|url| get_document(http, &url, config, crawler, cancellation, attempts))
+    .and_then(|document| {
+      document.select_first("body").map_err(|()| Error {
+        message: "Detail page has no body.".to_owned(),
+      })
+    })
+    .and_then(|body| Ok(crawler.transform_detail(body, data)?));
+  match detail_result {
+    Ok(enriched_data) => flat.fill(&enriched_data),
+    Err(e) => {
+      crawler.log(format!(
+        "could not fetch details from '{}', because: {}",
+        detail_url, e.message
+      ));
+      flat
+    }
+  }
+}
+
+fn next_page_url(
+  document: &NodeRef,
+  first_url: &Url,
+  current_url: &Url,
+  page: u32,
+  crawler: &dyn Crawler,
+) -> Option<Url> {
+  match crawler.pagination() {
+    Pagination::None => None,
+    Pagination::NextLink(selector) => document
+      .select_first(selector)
+      .ok()
+      .and_then(|link| {
+        link
+          .attributes
+          .borrow()
+          .get("href")
+          .map(|href| href.to_owned())
+      })
+      .and_then(|href| current_url.join(&href).ok()),
+    Pagination::PageParameter(parameter) => {
+      let mut url = first_url.clone();
+      let pairs: Vec<(String, String)> = first_url
+        .query_pairs()
+        .filter(|(key, _)| key != parameter)
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+      url
+        .query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair(parameter, &(page + 1).to_string());
+      Some(url)
+    }
+  }
+}
 
+fn decode_response(page: &Page, fallback: Option<Encoding>) -> String {
+  Encoding::detect(page.content_type(), &page.body, fallback).decode(&page.body)
+}
+
+fn get_document(
+  http: &Http,
+  url: &Url,
+  config: &Config,
+  crawler: &dyn Crawler,
+  cancellation: &Cancellation,
+  attempts: &mut u32,
+) -> Result<NodeRef, Error> {
   crawler.log(format!(">> sending request to url '{}' ... ", url));
-  let mut response = reqwest::get(url.as_str())?;
-  crawler.log(format!("<< received response."));
+  let page = http.get(url, &config.request, cancellation, attempts)?;
+  crawler.log("<< received response.".to_owned());
 
-  crawler.log(format!("parsing document ..."));
-  let decoded_response = decode_response(&mut response, &config.encoding)?;
+  crawler.log("parsing document ...".to_owned());
+  let decoded_response = decode_response(&page, config.encoding);
   let document = kuchiki::parse_html()
     .from_utf8()
     .read_from(&mut decoded_response.as_bytes())?;
-  crawler.log(format!("document parsed successfully."));
+  crawler.log("document parsed successfully.".to_owned());
+
+  Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{canonical_url, next_page_url};
+  use crate::crawlers::{ImmoScout, WGGesucht};
+  use kuchiki::traits::*;
+  use url::Url;
+
+  #[test]
+  fn finds_next_page_by_parameter() {
+    let crawler = ImmoScout {};
+    let document = kuchiki::parse_html().one("<html></html>");
+    let first_url =
+      Url::parse("http://www.immobilienscout24.de/Suche/Muenchen?pagerReporting=true").unwrap();
+    let second_url = next_page_url(&document, &first_url, &first_url, 1, &crawler).unwrap();
+    let third_url = next_page_url(&document, &first_url, &second_url, 2, &crawler).unwrap();
+
+    assert_eq!(
+      second_url.as_str(),
+      "http://www.immobilienscout24.de/Suche/Muenchen?pagerReporting=true&pagenumber=2"
+    );
+    assert_eq!(
+      third_url.as_str(),
+      "http://www.immobilienscout24.de/Suche/Muenchen?pagerReporting=true&pagenumber=3"
+    );
+  }
+
+  #[test]
+  fn finds_next_page_by_link() {
+    let crawler = WGGesucht {};
+    let url = Url::parse("http://www.wg-gesucht.de/wohnungen-in-Muenchen.90.2.0.0.html").unwrap();
+    let document = kuchiki::parse_html()
+      .one(r#"<a class="next" href="wohnungen-in-Muenchen.90.2.1.1.html">&gt;</a>"#);
+    let last_page = kuchiki::parse_html().one("<span>no more pages</span>");
+
+    assert_eq!(
+      next_page_url(&document, &url, &url, 1, &crawler)
+        .unwrap()
+        .as_str(),
+      "http://www.wg-gesucht.de/wohnungen-in-Muenchen.90.2.1.1.html"
+    );
+    assert!(next_page_url(&last_page, &url, &url, 1, &crawler).is_none());
+  }
+
+  #[test]
+  fn canonicalizes_urls() {
+    let page_url =
+      Url::parse("http://www.wg-gesucht.de/wohnungen-in-Muenchen.90.2.0.0.html?page=1").unwrap();
 
-  match document.select(crawler.selector()) {
-    Ok(x) => Ok(x),
-    Err(()) => Err(Error {
-      message: "Main selector did not match.".to_owned(),
-    }),
+    assert_eq!(
+      canonical_url(&page_url, "wohnungen-in-Muenchen.123.html").unwrap(),
+      "http://www.wg-gesucht.de/wohnungen-in-Muenchen.123.html"
+    );
+    assert_eq!(
+      canonical_url(
+        &page_url,
+        "/expose/123?utm_source=list&referrer=RESULT_LIST_LISTING&pagerReporting=true#/"
+      )
+      .unwrap(),
+      "http://www.wg-gesucht.de/expose/123"
+    );
+    assert_eq!(
+      canonical_url(
+        &page_url,
+        "https://www.wohnungsboerse.net/immodetail/123?id=4&gclid=5"
+      )
+      .unwrap(),
+      "https://www.wohnungsboerse.net/immodetail/123?id=4"
+    );
   }
 }
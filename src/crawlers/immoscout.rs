@@ -2,7 +2,7 @@ extern crate kuchiki;
 extern crate reqwest;
 extern crate std;
 
-use super::{Crawler, Error};
+use super::{Crawler, Error, Pagination};
 use crate::models::FlatData;
 use kuchiki::{ElementData, NodeDataRef};
 
@@ -17,6 +17,10 @@ impl Crawler for ImmoScout {
     "article[data-item=result]"
   }
 
+  fn pagination(&self) -> Pagination {
+    Pagination::PageParameter("pagenumber")
+  }
+
   fn transform_result(&self, result: NodeDataRef<ElementData>) -> Result<FlatData, Error> {
     let rent = Self::get_text(&result, ".result-list-entry__criteria dl:nth-child(1) dd")?;
     let squaremeters = Self::get_text(&result, ".result-list-entry__criteria dl:nth-child(2) dd")?;
@@ -29,13 +33,44 @@ impl Crawler for ImmoScout {
     let externalid = Self::get_attr(&result, None, "data-obid")?
       .trim()
       .to_owned();
+    let features = Self::find_texts(&result, ".result-list-entry__secondary-criteria li");
     Ok(FlatData {
+      furnished: Self::parse_furnished(&title),
+      balcony: Self::mentions(&features, "balkon"),
+      kitchen: Self::mentions(&features, "einbauküche"),
       rent: Self::parse_number(rent)?,
       squaremeters: Self::parse_number(squaremeters)?,
       address,
       title,
       rooms: Self::parse_number(rooms)?,
       externalid,
+      ..Default::default()
+    })
+  }
+
+  fn detail_url(&self, data: &FlatData) -> Option<String> {
+    Some(format!("/expose/{}", data.externalid))
+  }
+
+  fn transform_detail(
+    &self,
+    detail: NodeDataRef<ElementData>,
+    data: FlatData,
+  ) -> Result<FlatData, Error> {
+    let floor = Self::find_text(&detail, ".is24qa-etage");
+    let balcony = Self::find_text(&detail, ".is24qa-balkon-terrasse-label").map(|_| true);
+    let kitchen = Self::find_text(&detail, ".is24qa-einbaukueche-label").map(|_| true);
+    Ok(FlatData {
+      warm_rent: Self::find_number(&detail, ".is24qa-gesamtmiete"),
+      floor: floor.and_then(|floor| Self::parse_floor(&floor)),
+      balcony: balcony.or(data.balcony),
+      kitchen: kitchen.or(data.kitchen),
+      deposit: Self::find_number(&detail, ".is24qa-kaution-o-genossenschaftsanteile"),
+      additional_costs: Self::find_number(&detail, ".is24qa-nebenkosten"),
+      available_from: Self::find_text(&detail, ".is24qa-bezugsfrei-ab"),
+      description: Self::find_text(&detail, ".is24qa-objektbeschreibung"),
+      images: Self::find_attrs(&detail, ".sp-image", "data-src"),
+      ..data
     })
   }
 }
@@ -3,7 +3,7 @@ extern crate regex;
 extern crate reqwest;
 extern crate std;
 
-use super::{Crawler, Error};
+use super::{Crawler, Error, Pagination};
 use crate::models::FlatData;
 use kuchiki::{ElementData, NodeDataRef};
 
@@ -13,9 +13,15 @@ pub struct ImmoWelt {
 
 impl ImmoWelt {
   pub fn new() -> Self {
-    return ImmoWelt {
+    ImmoWelt {
       brackets: regex::Regex::new(r"\s*\([^)]*\)").unwrap(),
-    };
+    }
+  }
+}
+
+impl Default for ImmoWelt {
+  fn default() -> Self {
+    Self::new()
   }
 }
 
@@ -28,26 +34,49 @@ impl Crawler for ImmoWelt {
     ".js-object[data-estateid]"
   }
 
+  fn pagination(&self) -> Pagination {
+    Pagination::PageParameter("cp")
+  }
+
   fn transform_result(&self, result: NodeDataRef<ElementData>) -> Result<FlatData, Error> {
     let rent = Self::get_text(&result, ".hardfacts_3 .hardfact:nth-child(1) strong")?;
+    let rent_label = Self::find_text(
+      &result,
+      ".hardfacts_3 .hardfact:nth-child(1) .hardfactlabel",
+    );
     let squaremeters = Self::get_text(&result, ".hardfacts_3 .hardfact:nth-child(2)")?;
     let rooms = Self::get_text(&result, ".hardfacts_3 .hardfact:nth-child(3)")?;
     let title = Self::get_text(&result, ".listcontent h2")?;
     let address = Self::get_text(&result, ".listlocation")?
       .split("\n")
       .map(|part| part.trim())
-      .filter(|part| part.len() > 0)
+      .filter(|part| !part.is_empty())
       .collect::<Vec<_>>()
       .join(", ");
     let cleaned_address = self.brackets.replace_all(&address, "").into_owned();
     let externalid = Self::get_attr(&result, None, "data-estateid")?;
+    let features = Self::find_texts(&result, ".listmerkmale li");
+    let rent = Self::parse_number(rent)?;
+    let is_warm_rent = match rent_label {
+      Some(label) => label.to_lowercase().contains("warm"),
+      None => false,
+    };
     Ok(FlatData {
-      rent: Self::parse_number(rent)?,
+      rent,
+      warm_rent: if is_warm_rent { Some(rent) } else { None },
+      furnished: Self::parse_furnished(&title).or(Self::mentions(&features, "möbliert")),
+      balcony: Self::mentions(&features, "balkon"),
+      kitchen: Self::mentions(&features, "einbauküche"),
       squaremeters: Self::parse_number(squaremeters)?,
       address: cleaned_address,
       title,
       rooms: Self::parse_number(rooms)?,
       externalid,
+      ..Default::default()
     })
   }
+
+  fn detail_url(&self, data: &FlatData) -> Option<String> {
+    Some(format!("/expose/{}", data.externalid))
+  }
 }
@@ -3,10 +3,9 @@ extern crate regex;
 extern crate reqwest;
 extern crate std;
 
-use super::{Crawler, Error};
+use super::{Crawler, Error, Pagination};
 use crate::models::FlatData;
 use kuchiki::{ElementData, NodeDataRef};
-use std::ops::Deref;
 
 pub struct Sueddeutsche {
   pub brackets: regex::Regex,
@@ -14,9 +13,15 @@ pub struct Sueddeutsche {
 
 impl Sueddeutsche {
   pub fn new() -> Self {
-    return Sueddeutsche {
+    Sueddeutsche {
       brackets: regex::Regex::new(r"\s*\([^)]*\)").unwrap(),
-    };
+    }
+  }
+}
+
+impl Default for Sueddeutsche {
+  fn default() -> Self {
+    Self::new()
   }
 }
 
@@ -29,10 +34,14 @@ impl Crawler for Sueddeutsche {
     "#idHitContent .hitRow"
   }
 
+  fn pagination(&self) -> Pagination {
+    Pagination::NextLink("a[rel=next]")
+  }
+
   fn transform_result<'a>(&self, result: NodeDataRef<ElementData>) -> Result<FlatData, Error> {
     let hit_rooms_div_text = Self::get_text(&result, ".hitRoomsDiv")?;
     let hit_rooms_div_elements: Vec<&str> = hit_rooms_div_text.split(", ").collect();
-    let squaremeters_opt: Option<&&str> = hit_rooms_div_elements.get(0);
+    let squaremeters_opt: Option<&&str> = hit_rooms_div_elements.first();
     let rooms_opt: Option<&&str> = hit_rooms_div_elements.get(1);
 
     let hit_regions_text = Self::get_text(&result, ".hitRegionTxt")?.replace("\t", "");
@@ -46,15 +55,20 @@ impl Crawler for Sueddeutsche {
     let rent = Self::get_text(&result, ".hitPrice")?.replace("&nbsp;", " ");
 
     let externalid = Self::get_attr(&result, None, "id")?.replace("idHitRowList", "");
+    let url = Self::get_attr(&result, Some("a.hitHeadline, .hitHeadline a"), "href").ok();
+    let furnished = Self::parse_furnished(&title);
 
     match (&squaremeters_opt, &rooms_opt, &address_opt) {
       (&Some(squaremeters), &Some(rooms), &Some(address)) => Ok(FlatData {
         rent: Self::parse_number(rent)?,
-        squaremeters: Self::parse_number(squaremeters.deref().to_owned())?,
-        address: self.brackets.replace_all(address.deref(), "").into_owned(),
+        squaremeters: Self::parse_number((*squaremeters).to_owned())?,
+        address: self.brackets.replace_all(address, "").into_owned(),
         title,
-        rooms: Self::parse_number(rooms.deref().to_owned())?,
+        rooms: Self::parse_number((*rooms).to_owned())?,
         externalid,
+        furnished,
+        url,
+        ..Default::default()
       }),
       _ => Err(Error {
         message: format!(
@@ -2,7 +2,7 @@ extern crate kuchiki;
 extern crate reqwest;
 extern crate std;
 
-use super::{Crawler, Error};
+use super::{Crawler, Error, Pagination};
 use crate::models::FlatData;
 use kuchiki::{ElementData, NodeDataRef};
 
@@ -17,8 +17,14 @@ impl Crawler for WGGesucht {
     "tr[adid^=wohnungen]"
   }
 
+  fn pagination(&self) -> Pagination {
+    Pagination::NextLink("a.next")
+  }
+
   fn transform_result(&self, result: NodeDataRef<ElementData>) -> Result<FlatData, Error> {
-    let only_limited = Self::get_text(&result, ".ang_spalte_freibis")?.trim().len() > 0;
+    let only_limited = !Self::get_text(&result, ".ang_spalte_freibis")?
+      .trim()
+      .is_empty();
     if only_limited {
       Err(Error {
         message: "Flat is only available for a limited time.".to_owned(),
@@ -34,12 +40,15 @@ impl Crawler for WGGesucht {
           .trim();
       let externalid = Self::get_attr(&result, None, "adid")?;
       Ok(FlatData {
+        available_from: Self::find_text(&result, ".ang_spalte_freiab"),
+        url: Self::get_attr(&result, Some("a[href]"), "href").ok(),
         rent: Self::parse_number(rent)?,
         squaremeters: Self::parse_number(squaremeters)?,
         address,
         title,
         rooms: Self::parse_number(rooms)?,
         externalid,
+        ..Default::default()
       })
     }
   }
@@ -2,7 +2,7 @@ extern crate kuchiki;
 extern crate reqwest;
 extern crate std;
 
-use super::{Crawler, Error};
+use super::{Crawler, Error, Pagination};
 use crate::models::FlatData;
 use kuchiki::{ElementData, NodeDataRef};
 
@@ -25,6 +25,10 @@ impl Crawler for Wohnungsboerse {
     ".search_result_entry[class*='estate_']"
   }
 
+  fn pagination(&self) -> Pagination {
+    Pagination::NextLink("a[rel=next]")
+  }
+
   fn transform_result(&self, result: NodeDataRef<ElementData>) -> Result<FlatData, Error> {
     let title = Self::get_text(&result, ".search_result_entry-headline")?
       .trim()
@@ -49,6 +53,7 @@ impl Crawler for Wohnungsboerse {
     )?;
     let link = Self::get_attr(&result, Some(".search_result_entry-headline a"), "href")?;
     let externalid_opt = link.rsplit("/").next();
+    let furnished = Self::parse_furnished(&title);
 
     match externalid_opt {
       Some(externalid) => Ok(FlatData {
@@ -58,10 +63,25 @@ impl Crawler for Wohnungsboerse {
         title,
         rooms: Self::parse_number(rooms)?,
         externalid: externalid.to_string(),
+        furnished,
+        url: Some(link.to_owned()),
+        ..Default::default()
       }),
       None => Err(Error {
         message: "Could not find an external id".to_string(),
       }),
     }
   }
+
+  fn transform_detail(
+    &self,
+    detail: NodeDataRef<ElementData>,
+    data: FlatData,
+  ) -> Result<FlatData, Error> {
+    Ok(FlatData {
+      description: Self::find_text(&detail, "[itemprop=description]"),
+      images: Self::find_attrs(&detail, "img[itemprop=image]", "src"),
+      ..data
+    })
+  }
 }
@@ -0,0 +1,109 @@
+use crate::models::{City, Flat};
+use std::collections::HashSet;
+
+/// Keeps track of the flats that have been seen so far.
+///
+/// A flat is considered to be known, if there either is a flat with the same
+/// city, source and external id, or a flat within the same city that has the
+/// same normalized title. This matches the semantics of `Flat::eq`, but
+/// answers in constant time instead of comparing against every known flat.
+#[derive(Default)]
+pub struct Dedup {
+  ids: HashSet<(City, String, String)>,
+  titles: HashSet<(City, String)>,
+}
+
+impl Dedup {
+  pub fn new() -> Dedup {
+    Dedup::default()
+  }
+
+  pub fn is_new(&self, flat: &Flat) -> bool {
+    let known_id = match id_key(flat) {
+      Some(key) => self.ids.contains(&key),
+      None => false,
+    };
+    let known_title = match title_key(flat) {
+      Some(key) => self.titles.contains(&key),
+      None => false,
+    };
+    !known_id && !known_title
+  }
+
+  pub fn remember(&mut self, flat: &Flat) {
+    if let Some(key) = id_key(flat) {
+      self.ids.insert(key);
+    }
+    if let Some(key) = title_key(flat) {
+      self.titles.insert(key);
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.ids.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.ids.is_empty()
+  }
+}
+
+fn id_key(flat: &Flat) -> Option<(City, String, String)> {
+  flat.data.as_ref().map(|data| {
+    (
+      flat.city.clone(),
+      flat.source.to_owned(),
+      data.externalid.to_owned(),
+    )
+  })
+}
+
+fn title_key(flat: &Flat) -> Option<(City, String)> {
+  flat
+    .normalized_title()
+    .map(|title| (flat.city.clone(), title))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Dedup;
+  use crate::models::{City, Flat, FlatData};
+
+  fn flat(city: &str, source: &str, externalid: &str, title: &str) -> Flat {
+    Flat::new(source.to_owned(), City::new(city)).fill(&FlatData {
+      rent: 100.,
+      squaremeters: 50.,
+      address: String::from("Some address"),
+      title: String::from(title),
+      externalid: String::from(externalid),
+      rooms: 2.,
+      ..Default::default()
+    })
+  }
+
+  #[test]
+  fn knows_flats_by_id() {
+    let mut dedup = Dedup::new();
+    dedup.remember(&flat("Munich", "immoscout", "1", "Some title"));
+    assert!(!dedup.is_new(&flat("Munich", "immoscout", "1", "Other title")));
+    assert!(dedup.is_new(&flat("Munich", "immowelt", "1", "Other title")));
+    assert!(dedup.is_new(&flat("Augsburg", "immoscout", "1", "Other title")));
+  }
+
+  #[test]
+  fn knows_flats_by_title() {
+    let mut dedup = Dedup::new();
+    dedup.remember(&flat("Munich", "immoscout", "1", "This is% some title!"));
+    assert!(!dedup.is_new(&flat("Munich", "immowelt", "2", "This is some title")));
+    assert!(dedup.is_new(&flat("Kempten", "immowelt", "2", "This is some title")));
+  }
+
+  #[test]
+  fn flats_without_data_are_always_new() {
+    let mut dedup = Dedup::new();
+    let empty = Flat::new("immoscout".to_owned(), City::new("Munich"));
+    dedup.remember(&empty);
+    assert!(dedup.is_new(&empty));
+    assert!(dedup.is_empty());
+  }
+}
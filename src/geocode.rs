@@ -3,6 +3,8 @@ extern crate serde;
 extern crate serde_json;
 extern crate url;
 
+use crate::crawlers::Cancellation;
+use crate::http::{Error as HttpError, Http, RequestSettings};
 use serde_derive::{Deserialize, Serialize};
 use std::f32;
 use std::num::ParseFloatError;
@@ -12,12 +14,23 @@ pub struct ApiResult {
   pub lat: String,
   pub lon: String,
   pub boundingbox: Vec<String>,
+  #[serde(default)]
+  pub address: Option<ApiAddress>,
+}
+
+/// The parts of the address that nominatim has found, only those that tell
+/// the district are of interest.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiAddress {
+  pub city_district: Option<String>,
+  pub suburb: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GeocodeResult {
   pub coord: Coordinate,
   pub uncertainty: f32,
+  pub district: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,7 +39,7 @@ pub struct Coordinate {
   pub longitude: f32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BoundingBox {
   pub min_lat: f32,
   pub max_lat: f32,
@@ -41,25 +54,33 @@ pub struct Error {
 
 impl From<reqwest::Error> for Error {
   fn from(_err: reqwest::Error) -> Error {
-    return Error {
+    Error {
       message: "Request Error".to_owned(),
-    };
+    }
+  }
+}
+
+impl From<HttpError> for Error {
+  fn from(err: HttpError) -> Error {
+    Error {
+      message: err.message,
+    }
   }
 }
 
 impl From<url::ParseError> for Error {
   fn from(_err: url::ParseError) -> Error {
-    return Error {
+    Error {
       message: "Parse Error".to_owned(),
-    };
+    }
   }
 }
 
 impl From<ParseFloatError> for Error {
   fn from(_err: ParseFloatError) -> Error {
-    return Error {
+    Error {
       message: "Number could not be parsed to float".to_owned(),
-    };
+    }
   }
 }
 
@@ -80,20 +101,38 @@ fn degree_to_radian(deg: f32) -> f32 {
   deg * (f32::consts::PI / 180.0)
 }
 
-pub fn geocode(nominatim_url: &String, address: &String) -> Result<GeocodeResult, Error> {
+pub fn geocode(
+  http: &Http,
+  request: &RequestSettings,
+  nominatim_url: &str,
+  address: &str,
+  preferred_area: Option<&BoundingBox>,
+  cancellation: &Cancellation,
+) -> Result<GeocodeResult, Error> {
   let mut url = url::Url::parse(nominatim_url)?;
-  url.query_pairs_mut().append_pair("q", address.as_str());
+  url.query_pairs_mut().append_pair("q", address);
   url.query_pairs_mut().append_pair("format", "json");
+  url.query_pairs_mut().append_pair("addressdetails", "1");
+  if let Some(area) = preferred_area {
+    url.query_pairs_mut().append_pair(
+      "viewbox",
+      &format!(
+        "{},{},{},{}",
+        area.min_lon, area.max_lat, area.max_lon, area.min_lat
+      ),
+    );
+  }
 
-  let response: Vec<ApiResult> = reqwest::get(url.as_str())?.json()?;
-
-  if response.len() >= 1 {
-    let best_match: &ApiResult = response.get(0).expect("Results have been empty!");
+  let mut attempts = 0;
+  let response: Vec<ApiResult> = http
+    .get(&url, request, cancellation, &mut attempts)?
+    .json()?;
 
+  if let Some(best_match) = response.first() {
     let bounds = match (
       best_match
         .boundingbox
-        .get(0)
+        .first()
         .map(|c: &String| c.parse::<f32>()),
       best_match
         .boundingbox
@@ -131,6 +170,12 @@ pub fn geocode(nominatim_url: &String, address: &String) -> Result<GeocodeResult
       (Some(c), Some(b)) => Ok(GeocodeResult {
         coord: c,
         uncertainty: get_distance_from_lat_lon_in_m(b.max_lat, b.max_lon, b.min_lat, b.min_lon),
+        district: best_match.address.as_ref().and_then(|address| {
+          address
+            .city_district
+            .clone()
+            .or_else(|| address.suburb.clone())
+        }),
       }),
       _ => Err(Error {
         message: "Could not geocode location!".to_owned(),
@@ -0,0 +1,427 @@
+extern crate chrono;
+extern crate rand;
+extern crate reqwest;
+extern crate serde_json;
+extern crate url;
+
+use crate::crawlers::Cancellation;
+use crate::ratelimit::{RateLimiter, RateLimits};
+use rand::Rng;
+use reqwest::header::{
+  HeaderMap, HeaderName, HeaderValue, ACCEPT_LANGUAGE, CONTENT_TYPE, RETRY_AFTER, USER_AGENT,
+};
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error as StdError;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+const DEFAULT_SCHEME: &str = "https";
+const DEFAULT_TIMEOUT_IN_SECONDS: u64 = 30;
+const DEFAULT_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_DELAY_IN_MILLISECONDS: u64 = 1000;
+const DEFAULT_MAX_RETRY_DELAY_IN_MILLISECONDS: u64 = 60_000;
+const DEFAULT_USER_AGENT: &str = concat!("flatcrawl-crawler/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug)]
+pub struct Error {
+  pub message: String,
+}
+
+impl From<reqwest::Error> for Error {
+  fn from(err: reqwest::Error) -> Error {
+    Error {
+      message: format!("Request Error: {}", err),
+    }
+  }
+}
+
+/// How requests will be sent. Every setting is optional, so that targets
+/// only need to mention what differs from the global settings.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RequestSettings {
+  pub scheme: Option<String>,
+  pub user_agent: Option<String>,
+  /// timeout in seconds
+  pub timeout: Option<u64>,
+  pub accept_language: Option<String>,
+  #[serde(default)]
+  pub headers: BTreeMap<String, String>,
+  /// how often a request will be sent before giving up
+  pub attempts: Option<u32>,
+  /// delay in milliseconds before the first retry, it doubles with every retry
+  pub retry_delay: Option<u64>,
+  /// upper limit for the delay between two attempts in milliseconds
+  pub max_retry_delay: Option<u64>,
+}
+
+impl RequestSettings {
+  /// Combines both settings, where the given ones take precedence.
+  pub fn merge(&self, overrides: &RequestSettings) -> RequestSettings {
+    let mut headers = self.headers.clone();
+    headers.extend(overrides.headers.clone());
+    RequestSettings {
+      scheme: overrides.scheme.clone().or_else(|| self.scheme.clone()),
+      user_agent: overrides
+        .user_agent
+        .clone()
+        .or_else(|| self.user_agent.clone()),
+      timeout: overrides.timeout.or(self.timeout),
+      accept_language: overrides
+        .accept_language
+        .clone()
+        .or_else(|| self.accept_language.clone()),
+      headers,
+      attempts: overrides.attempts.or(self.attempts),
+      retry_delay: overrides.retry_delay.or(self.retry_delay),
+      max_retry_delay: overrides.max_retry_delay.or(self.max_retry_delay),
+    }
+  }
+
+  pub fn scheme(&self) -> &str {
+    self.scheme.as_deref().unwrap_or(DEFAULT_SCHEME)
+  }
+
+  pub fn timeout(&self) -> Duration {
+    Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT_IN_SECONDS))
+  }
+
+  pub fn attempts(&self) -> u32 {
+    self.attempts.unwrap_or(DEFAULT_ATTEMPTS)
+  }
+
+  /// Exponential backoff with jitter for the retry that follows the given
+  /// attempt, somewhere between half and the full doubled delay.
+  pub fn retry_delay(&self, attempt: u32) -> Duration {
+    let base = self
+      .retry_delay
+      .unwrap_or(DEFAULT_RETRY_DELAY_IN_MILLISECONDS);
+    let delay = base
+      .saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)))
+      .min(self.max_retry_delay());
+    Duration::from_millis(rand::thread_rng().gen_range(delay / 2, delay + 1))
+  }
+
+  fn max_retry_delay(&self) -> u64 {
+    self
+      .max_retry_delay
+      .unwrap_or(DEFAULT_MAX_RETRY_DELAY_IN_MILLISECONDS)
+  }
+
+  /// Lists everything that is wrong with the settings.
+  pub fn validate(&self) -> Vec<String> {
+    let mut errors = Vec::new();
+    if self.scheme() != "http" && self.scheme() != "https" {
+      errors.push(format!(
+        "unknown scheme '{}', expected one of: http, https",
+        self.scheme()
+      ));
+    }
+    if self.timeout == Some(0) {
+      errors.push("'timeout' has to be at least 1 second".to_owned());
+    }
+    if self.attempts == Some(0) {
+      errors.push("'attempts' has to be at least 1".to_owned());
+    }
+    for (name, value) in self.headers.iter() {
+      if HeaderName::from_bytes(name.as_bytes()).is_err() {
+        errors.push(format!("invalid header name '{}'", name));
+      }
+      if HeaderValue::from_str(value).is_err() {
+        errors.push(format!("invalid value for header '{}'", name));
+      }
+    }
+    for (key, value) in [
+      ("user_agent", &self.user_agent),
+      ("accept_language", &self.accept_language),
+    ]
+    .iter()
+    {
+      if let Some(value) = value {
+        if HeaderValue::from_str(value).is_err() {
+          errors.push(format!("invalid value for '{}'", key));
+        }
+      }
+    }
+    errors
+  }
+}
+
+/// A response whose body has been read completely.
+pub struct Page {
+  pub status: StatusCode,
+  pub headers: HeaderMap,
+  pub body: Vec<u8>,
+}
+
+impl Page {
+  pub fn content_type(&self) -> Option<&str> {
+    self
+      .headers
+      .get(CONTENT_TYPE)
+      .and_then(|value| value.to_str().ok())
+  }
+
+  pub fn json<T: DeserializeOwned>(&self) -> Result<T, Error> {
+    serde_json::from_slice(&self.body).map_err(|e| Error {
+      message: format!("Request Error: invalid JSON: {}", e),
+    })
+  }
+}
+
+/// Sends all requests of the application.
+///
+/// The blocking client of reqwest only knows a single timeout, so there is
+/// one shared client for each distinct timeout that has been configured.
+/// Requests to the same host are throttled according to its rate limit.
+pub struct Http {
+  clients: HashMap<Duration, Client>,
+  limiter: Arc<RateLimiter>,
+}
+
+impl Http {
+  pub fn new<'a, I>(settings: I, limits: RateLimits) -> Result<Http, Error>
+  where
+    I: IntoIterator<Item = &'a RequestSettings>,
+  {
+    Http::with_limiter(settings, Arc::new(RateLimiter::new(limits)))
+  }
+
+  /// Creates the clients for new settings, which share the rate limiter of
+  /// this one, so that neither its state nor the requests that are still
+  /// running are forgotten.
+  pub fn reconfigure<'a, I>(&self, settings: I, limits: RateLimits) -> Result<Http, Error>
+  where
+    I: IntoIterator<Item = &'a RequestSettings>,
+  {
+    let http = Http::with_limiter(settings, self.limiter.clone())?;
+    self.limiter.set_limits(limits);
+    Ok(http)
+  }
+
+  fn with_limiter<'a, I>(settings: I, limiter: Arc<RateLimiter>) -> Result<Http, Error>
+  where
+    I: IntoIterator<Item = &'a RequestSettings>,
+  {
+    let mut clients = HashMap::new();
+    for timeout in settings.into_iter().map(|settings| settings.timeout()) {
+      if let Entry::Vacant(entry) = clients.entry(timeout) {
+        entry.insert(Client::builder().timeout(timeout).build()?);
+      }
+    }
+    Ok(Http { clients, limiter })
+  }
+
+  /// Sends a GET request and retries it on timeouts, dropped connections,
+  /// 429 and 5xx responses. Every request that has been sent is counted
+  /// within `attempts`. The host stays occupied until the body has been read.
+  /// Once `cancellation` has been triggered, no further attempts are made.
+  pub fn get(
+    &self,
+    url: &Url,
+    settings: &RequestSettings,
+    cancellation: &Cancellation,
+    attempts: &mut u32,
+  ) -> Result<Page, Error> {
+    let mut attempt = 0;
+    loop {
+      if cancellation.is_cancelled() {
+        return Err(Error {
+          message: "Request Error: request has been cancelled".to_owned(),
+        });
+      }
+      attempt += 1;
+      *attempts += 1;
+      let response = {
+        let _permit = url.host_str().map(|host| self.limiter.acquire(host));
+        self.send(url, settings)
+      };
+      let (error, retry_after) = match response {
+        Ok(page) if !is_retryable_status(page.status) => return Ok(page),
+        Ok(page) => (
+          format!("Request Error: server responded with {}", page.status),
+          retry_after(&page.headers),
+        ),
+        Err(e) if is_retryable_error(&e) => (format!("Request Error: {}", e), None),
+        Err(e) => return Err(Error::from(e)),
+      };
+      if attempt >= settings.attempts() {
+        return Err(Error {
+          message: format!("{} (gave up after {} attempts)", error, attempt),
+        });
+      }
+      let delay = match retry_after {
+        Some(retry_after) if retry_after > Duration::from_millis(settings.max_retry_delay()) => {
+          return Err(Error {
+            message: format!(
+              "{} (asked to retry after {} seconds)",
+              error,
+              retry_after.as_secs()
+            ),
+          });
+        }
+        Some(retry_after) => retry_after,
+        None => settings.retry_delay(attempt),
+      };
+      if cancellation.sleep(delay) {
+        return Err(Error {
+          message: format!("{} (cancelled before attempt {})", error, attempt + 1),
+        });
+      }
+    }
+  }
+
+  fn send(&self, url: &Url, settings: &RequestSettings) -> Result<Page, reqwest::Error> {
+    let client = match self.clients.get(&settings.timeout()) {
+      Some(client) => client.clone(),
+      None => Client::builder().timeout(settings.timeout()).build()?,
+    };
+    let mut request = client.get(url.as_str()).header(
+      USER_AGENT,
+      settings.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT),
+    );
+    if let Some(accept_language) = &settings.accept_language {
+      request = request.header(ACCEPT_LANGUAGE, accept_language.as_str());
+    }
+    for (name, value) in settings.headers.iter() {
+      request = request.header(name.as_str(), value.as_str());
+    }
+    let mut response = request.send()?;
+    let mut body = Vec::new();
+    response.copy_to(&mut body)?;
+    Ok(Page {
+      status: response.status(),
+      headers: response.headers().clone(),
+      body,
+    })
+  }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+  status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Timeouts and connections that have been dropped are worth another try,
+/// anything else (e.g. an unknown host) will fail again anyway.
+fn is_retryable_error(err: &reqwest::Error) -> bool {
+  if err.is_timeout() {
+    return true;
+  }
+  let mut source: Option<&(dyn StdError + 'static)> = err.source();
+  while let Some(cause) = source {
+    if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+      match io_error.kind() {
+        io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::BrokenPipe
+        | io::ErrorKind::TimedOut
+        | io::ErrorKind::UnexpectedEof => return true,
+        _ => (),
+      }
+    }
+    source = cause.source();
+  }
+  false
+}
+
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+  headers
+    .get(RETRY_AFTER)
+    .and_then(|value| value.to_str().ok())
+    .and_then(parse_retry_after)
+}
+
+/// Reads the `Retry-After` header, which is either a number of seconds or a date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+  if let Ok(seconds) = value.trim().parse::<u64>() {
+    return Some(Duration::from_secs(seconds));
+  }
+  let date = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+  let seconds = date.timestamp() - chrono::Utc::now().timestamp();
+  Some(Duration::from_secs(seconds.max(0) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{parse_retry_after, RequestSettings};
+  use std::time::Duration;
+
+  #[test]
+  fn merges_settings() {
+    let mut global = RequestSettings {
+      user_agent: Some("global agent".to_owned()),
+      accept_language: Some("de-DE".to_owned()),
+      ..Default::default()
+    };
+    global.headers.insert("X-Global".to_owned(), "1".to_owned());
+    global.headers.insert("X-Both".to_owned(), "1".to_owned());
+    let mut target = RequestSettings {
+      user_agent: Some("target agent".to_owned()),
+      timeout: Some(5),
+      ..Default::default()
+    };
+    target.headers.insert("X-Both".to_owned(), "2".to_owned());
+
+    let merged = global.merge(&target);
+    assert_eq!(merged.scheme(), "https");
+    assert_eq!(merged.user_agent.as_deref(), Some("target agent"));
+    assert_eq!(merged.accept_language.as_deref(), Some("de-DE"));
+    assert_eq!(merged.timeout(), Duration::from_secs(5));
+    assert_eq!(merged.headers["X-Global"], "1");
+    assert_eq!(merged.headers["X-Both"], "2");
+  }
+
+  #[test]
+  fn validates_settings() {
+    let mut settings = RequestSettings {
+      scheme: Some("ftp".to_owned()),
+      timeout: Some(0),
+      ..Default::default()
+    };
+    settings
+      .headers
+      .insert("Invalid Header".to_owned(), "1".to_owned());
+
+    assert_eq!(
+      settings.validate(),
+      vec![
+        "unknown scheme 'ftp', expected one of: http, https",
+        "'timeout' has to be at least 1 second",
+        "invalid header name 'Invalid Header'",
+      ]
+    );
+  }
+
+  #[test]
+  fn backs_off_exponentially() {
+    let settings = RequestSettings {
+      retry_delay: Some(1000),
+      max_retry_delay: Some(5000),
+      ..Default::default()
+    };
+
+    for _ in 0..20 {
+      let first = settings.retry_delay(1);
+      let second = settings.retry_delay(2);
+      let tenth = settings.retry_delay(10);
+      assert!(first >= Duration::from_millis(500) && first <= Duration::from_millis(1000));
+      assert!(second >= Duration::from_millis(1000) && second <= Duration::from_millis(2000));
+      assert!(tenth >= Duration::from_millis(2500) && tenth <= Duration::from_millis(5000));
+    }
+  }
+
+  #[test]
+  fn parses_retry_after() {
+    assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+    assert_eq!(
+      parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+      Some(Duration::from_secs(0))
+    );
+    assert_eq!(parse_retry_after("soon"), None);
+  }
+}
@@ -0,0 +1,13 @@
+pub mod configuration;
+pub mod crawlers;
+pub mod dedup;
+pub mod geocode;
+pub mod http;
+pub mod models;
+pub mod pipeline;
+pub mod ratelimit;
+pub mod reload;
+pub mod schedule;
+pub mod shutdown;
+pub mod sinks;
+pub mod store;
@@ -1,37 +1,185 @@
-mod configuration;
-mod crawlers;
-mod geocode;
-mod models;
-
-use crate::lapin::options::{BasicPublishOptions, ExchangeDeclareOptions};
-use crate::lapin::types::FieldTable;
-use crate::lapin::{BasicProperties, Client, ConnectionProperties, ExchangeKind};
-use crate::models::Flat;
-use configuration::ApplicationConfig;
-use crawlers::Config;
-use futures::future::Future;
-use lapin_futures as lapin;
-use std::sync::Mutex;
-use std::sync::{Arc, Barrier};
-use std::thread;
-use std::thread::JoinHandle;
-use std::time::Instant;
+use chrono::Local;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use flatcrawl_crawler::configuration::{self, ApplicationConfig};
+use flatcrawl_crawler::crawlers::Cancellation;
+use flatcrawl_crawler::dedup::Dedup;
+use flatcrawl_crawler::geocode;
+use flatcrawl_crawler::http::{Error as HttpError, Http, RequestSettings};
+use flatcrawl_crawler::models::{self, Flat};
+use flatcrawl_crawler::pipeline::{self, IsKnown, Running, TargetResult};
+use flatcrawl_crawler::reload::ConfigWatcher;
+use flatcrawl_crawler::schedule::Scheduler;
+use flatcrawl_crawler::shutdown::Shutdown;
+use flatcrawl_crawler::sinks::{self, Sink, Sinks};
+use flatcrawl_crawler::store::Store;
+use std::process;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
 
 fn main() {
-  let app_config = configuration::read();
-  let mut init_run = if app_config.test { false } else { true };
-  let amqp_host = app_config.amqp_config.host.to_owned();
-  let thread_count = app_config.thread_count as usize;
+  let matches = App::new("flatcrawl")
+    .version(env!("CARGO_PKG_VERSION"))
+    .about("Crawls real estate sites and passes on every flat it has not seen before.")
+    .arg(
+      Arg::with_name("config")
+        .long("config")
+        .value_name("PATH")
+        .default_value("config")
+        .global(true)
+        .help("Configuration file, its extension may be left out"),
+    )
+    .arg(
+      Arg::with_name("dry-run")
+        .long("dry-run")
+        .global(true)
+        .help("Prints new flats instead of sending them and does not remember them"),
+    )
+    .subcommand(
+      SubCommand::with_name("run")
+        .about("Crawls the targets by their schedules until stopped (default)"),
+    )
+    .subcommand(SubCommand::with_name("once").about("Crawls every target once"))
+    .subcommand(
+      SubCommand::with_name("crawl")
+        .about("Prints the flats of a single target as JSON lines")
+        .arg(
+          Arg::with_name("target")
+            .required(true)
+            .help("The target as shown by list-targets"),
+        ),
+    )
+    .subcommand(SubCommand::with_name("list-targets").about("Lists the configured targets"))
+    .subcommand(
+      SubCommand::with_name("geocode")
+        .about("Looks up the coordinates of an address")
+        .arg(
+          Arg::with_name("city")
+            .long("city")
+            .value_name("CITY")
+            .help("Prefers results within this city"),
+        )
+        .arg(Arg::with_name("address").required(true).multiple(true)),
+    )
+    .subcommand(SubCommand::with_name("validate-config").about("Checks the configuration"))
+    .subcommand(
+      SubCommand::with_name("schema").about("Prints the JSON Schema of the flats that are sent"),
+    )
+    .get_matches();
+  let (command, command_matches) = matches.subcommand();
+  let args = command_matches.unwrap_or(&matches);
+  if command == "schema" {
+    match serde_json::to_string_pretty(&Flat::json_schema()) {
+      Ok(schema) => println!("{}", schema),
+      Err(e) => {
+        eprintln!("could not serialize schema: {}", e);
+        process::exit(1);
+      }
+    }
+    return;
+  }
+
+  let config_path = args.value_of("config").unwrap_or("config");
+  let app_config = match configuration::read(config_path) {
+    Ok(app_config) => app_config,
+    Err(e) => {
+      eprintln!("invalid configuration:\n{}", e.message);
+      process::exit(1);
+    }
+  };
+  let dry_run = args.is_present("dry-run");
+  match command {
+    "once" => run(config_path, app_config, dry_run, true),
+    "crawl" => crawl(&app_config, args),
+    "list-targets" => list_targets(&app_config),
+    "geocode" => geocode_address(&app_config, args),
+    "validate-config" => println!(
+      "configuration is valid and has {} targets.",
+      app_config.targets.len()
+    ),
+    _ => run(config_path, app_config, dry_run, false),
+  }
+}
+
+fn create_http(app_config: &ApplicationConfig) -> Result<Arc<Http>, HttpError> {
+  Http::new(request_settings(app_config), app_config.rate_limits.clone()).map(Arc::new)
+}
+
+fn request_settings(app_config: &ApplicationConfig) -> impl Iterator<Item = &RequestSettings> {
+  std::iter::once(&app_config.request).chain(app_config.targets.iter().map(|t| &t.request))
+}
+
+fn create_http_or_exit(app_config: &ApplicationConfig) -> Arc<Http> {
+  match create_http(app_config) {
+    Ok(http) => http,
+    Err(e) => {
+      eprintln!("could not create http client: {}", e.message);
+      process::exit(1);
+    }
+  }
+}
+
+fn create_runtime() -> Runtime {
+  match Runtime::new() {
+    Ok(runtime) => runtime,
+    Err(e) => {
+      eprintln!("could not start runtime: {}", e);
+      process::exit(1);
+    }
+  }
+}
+
+/// Reads the configuration again, along with the http client it needs, which
+/// keeps the rate limiter of the current one. The sinks are only created
+/// again if their configuration has changed, so that open connections are
+/// kept.
+fn reload(
+  config_path: &str,
+  current: &ApplicationConfig,
+  current_http: &Http,
+  shutdown: &Shutdown,
+) -> Result<(ApplicationConfig, Arc<Http>, Option<Sinks>), String> {
+  let app_config = configuration::read(config_path).map_err(|e| e.message)?;
+  let sinks = if app_config.sinks != current.sinks || app_config.test != current.test {
+    let sinks = sinks::create(&app_config.sinks, app_config.test, &shutdown.requested())
+      .map_err(|e| format!("could not create sinks: {}", e.message))?;
+    Some(sinks)
+  } else {
+    None
+  };
+  let http = current_http
+    .reconfigure(
+      request_settings(&app_config),
+      app_config.rate_limits.clone(),
+    )
+    .map_err(|e| format!("could not create http client: {}", e.message))?;
+  Ok((app_config, Arc::new(http), sinks))
+}
 
-  if app_config.test {
+/// Crawls the targets when they are due, or all of them just `once`, and
+/// passes on the flats that have not been seen before. Unless it runs just
+/// once, changes of the configuration are applied before the next cycle.
+fn run(config_path: &str, mut app_config: ApplicationConfig, dry_run: bool, once: bool) {
+  let mut http = create_http_or_exit(&app_config);
+  let shutdown = Shutdown::new();
+  let mut sinks = match sinks::create(&app_config.sinks, app_config.test, &shutdown.requested()) {
+    Ok(sinks) => sinks,
+    Err(e) => {
+      eprintln!("could not create sinks: {}", e.message);
+      process::exit(1);
+    }
+  };
+
+  if app_config.test && !dry_run {
     println!("----- Running in TEST mode! -----");
     let flats = vec![Flat {
-      city: models::City::Munich,
+      city: models::City::new("Munich"),
       source: "immoscout".to_owned(),
       location: Some(models::Location {
         latitude: 9.0,
         longitude: 10.0,
         uncertainty: 0.0,
+        district: Some("Altstadt-Lehel".to_owned()),
       }),
       data: Some(models::FlatData {
         address: "Some address".to_owned(),
@@ -40,125 +188,349 @@ fn main() {
         rooms: 2.0,
         squaremeters: 60.0,
         title: "Test Flat".to_owned(),
+        ..Default::default()
       }),
       date: 0,
     }];
     println!("flat: {}", serde_json::to_string(&flats[0]).unwrap());
-    send_results(&app_config, amqp_host.as_str(), flats);
+    send_results(&mut sinks, &flats);
   }
 
-  let barrier = Arc::new(Barrier::new(thread_count + 1));
-  let mut last_flats = Vec::<Flat>::new();
-  loop {
-    let crawl_start = Instant::now();
-    let guarded_configs = Arc::new(Mutex::new(crawlers::get_crawler_configs()));
+  let store = match Store::open(&app_config.store_path) {
+    Ok(store) => store,
+    Err(e) => {
+      eprintln!(
+        "could not open store at '{}': {}",
+        app_config.store_path, e.message
+      );
+      process::exit(1);
+    }
+  };
 
-    // process all crawlers
-    let mut thread_handles: Vec<JoinHandle<Vec<Flat>>> = vec![];
-    for i in 0..thread_count {
-      let inner_guarded_configs = guarded_configs.clone();
-      let inner_barrier = barrier.clone();
-      let cap_conf = app_config.clone();
-      let handle = thread::spawn(move || {
-        let flats = run_thread(inner_guarded_configs, i, &cap_conf);
-        inner_barrier.wait();
-        flats
-      });
-      &mut thread_handles.push(handle);
+  let mut dedup = Dedup::new();
+  match store.flats() {
+    Ok(flats) => {
+      for flat in flats.iter() {
+        dedup.remember(flat);
+      }
+      println!("loaded {} known flats.", dedup.len());
     }
+    Err(e) => {
+      eprintln!("could not load known flats: {}", e.message);
+      process::exit(1);
+    }
+  }
 
-    // wait for all threads to finish
-    barrier.wait();
+  // an empty store means that we have never seen any flats,
+  // so the first run will only be used to fill it up
+  let mut init_run = !app_config.test && dedup.is_empty();
+  let guarded_dedup = Arc::new(RwLock::new(dedup));
 
-    // collect results
-    let flats = thread_handles
-      .into_iter()
-      .map(|h| h.join().unwrap_or_default())
-      .flatten()
-      .collect::<Vec<_>>();
+  let mut runtime = create_runtime();
+  // on SIGTERM or SIGINT, the current cycle is finished, while the running
+  // crawls are given some time before they are cancelled, a second signal
+  // ends the process right away
+  if let Err(e) = shutdown.on_signals() {
+    eprintln!("could not handle signals: {}", e.message);
+    process::exit(1);
+  }
+  shutdown.cancel_after(app_config.shutdown_timeout);
+  shutdown.exit_after(app_config.shutdown_timeout + app_config.exit_timeout);
 
-    // filter results for duplicates
-    let mut filtered_flats: Vec<_> = Vec::new();
-    println!("successfully parsed {} flats.", flats.len());
-    for current_flat in flats.to_vec() {
-      let has_been_sent = last_flats
-        .to_vec()
-        .into_iter()
-        .any(|previous_flat| previous_flat == current_flat);
-      if !has_been_sent {
-        filtered_flats.push(current_flat);
+  // on SIGHUP or once its files have changed, the configuration is read
+  // again, but kept as it is if the new one turns out to be invalid
+  let mut watcher = ConfigWatcher::new(configuration::files(config_path));
+  if !once {
+    if let Err(e) = watcher.on_signal() {
+      eprintln!("could not handle signals: {}", e.message);
+      process::exit(1);
+    }
+  }
+
+  let mut scheduler = Scheduler::new(app_config.targets.clone(), Instant::now());
+  let running = Running::default();
+  while !shutdown.is_requested() {
+    if !once && watcher.has_changed() {
+      match reload(config_path, &app_config, &http, &shutdown) {
+        Ok((new_config, new_http, new_sinks)) => {
+          if new_config.store_path != app_config.store_path
+            || new_config.shutdown_timeout != app_config.shutdown_timeout
+            || new_config.exit_timeout != app_config.exit_timeout
+          {
+            println!(
+              "changes of store_path, shutdown_timeout and exit_timeout take effect after a restart."
+            );
+          }
+          scheduler.update(new_config.targets.clone(), Instant::now());
+          http = new_http;
+          if let Some(new_sinks) = new_sinks {
+            sinks = new_sinks;
+          }
+          app_config = new_config;
+          println!(
+            "reloaded configuration with {} targets.",
+            app_config.targets.len()
+          );
+        }
+        Err(message) => eprintln!(
+          "keeping the previous configuration, as the new one is invalid:\n{}",
+          message
+        ),
       }
     }
+    let due_targets = if once {
+      app_config.targets.clone()
+    } else {
+      scheduler.due(Instant::now(), &Local::now().naive_local())
+    };
+    if due_targets.is_empty() {
+      // targets that are due, but within their quiet hours are looked at
+      // again every minute
+      let wait = scheduler.time_until_next_run(Instant::now());
+      shutdown.sleep(if wait == Duration::from_secs(0) {
+        Duration::from_secs(60)
+      } else {
+        wait.min(Duration::from_secs(60))
+      });
+      continue;
+    }
+    let crawl_start = Instant::now();
+    let crawled_targets = due_targets.len();
+
+    // crawl all due targets
+    let inner_guarded_dedup = guarded_dedup.clone();
+    let is_known: IsKnown =
+      Arc::new(move |flat: &Flat| !inner_guarded_dedup.read().unwrap().is_new(flat));
+    let target_results = runtime
+      .block_on(pipeline::crawl(
+        http.clone(),
+        due_targets,
+        app_config.concurrency,
+        is_known,
+        running.clone(),
+        shutdown.clone(),
+      ))
+      .unwrap_or_default();
+    let (flats, filtered_flats) = collect_flats(
+      target_results,
+      &app_config,
+      &guarded_dedup.read().unwrap(),
+      &mut scheduler,
+      init_run,
+    );
+    println!("successfully parsed {} flats.", flats.len());
 
     let run_duration = crawl_start.elapsed();
     println!(
-      "analyzed {} pages and found {} flats in {}.{} seconds.",
-      crawlers::get_crawler_configs().len(),
+      "crawled {} targets and found {} flats in {}.{} seconds.",
+      crawled_targets,
       flats.len(),
       run_duration.as_secs(),
       run_duration.subsec_millis()
     );
+    if !once {
+      let intervals: Vec<String> = scheduler
+        .intervals()
+        .iter()
+        .map(|(id, interval)| format!("'{}' every {} seconds", id, interval.as_secs()))
+        .collect();
+      println!("crawling {}.", intervals.join(", "));
+    }
 
     // in the first run, we will collect
+    let mut new_flats = Vec::new();
     if init_run {
       init_run = false;
       println!("during initial run, we do not send flats ...");
     } else {
       // geocode all new flats
-      let geocoded_flats = geocode_flats(&filtered_flats, &app_config);
+      let geocoded_flats = geocode_flats(
+        &filtered_flats,
+        &app_config,
+        &http,
+        &shutdown.cancellation(),
+      );
 
       // only send new flats
-      if app_config.test {
+      if app_config.test || dry_run {
         for flat in geocoded_flats {
           println!("flat that would be send: {:?}", flat);
           println!("run finished.");
         }
       } else {
-        println!("will be sending {} flats ...", geocoded_flats.len());
-        send_results(&app_config, amqp_host.as_str(), geocoded_flats);
-        println!("done.");
+        new_flats = geocoded_flats;
+      }
+    }
+
+    // put the new flats into the outboxes before they are remembered, so
+    // that a crash in between does not lose them ...
+    let mut enqueued = true;
+    if !app_config.test && !dry_run {
+      let ids: Vec<&str> = sinks.iter().map(|(id, _)| id.as_str()).collect();
+      if let Err(e) = store.enqueue(&ids, &new_flats) {
+        eprintln!(
+          "could not put flats into the outboxes, so they are not remembered either: {}",
+          e.message
+        );
+        enqueued = false;
       }
     }
 
-    // remember the flats so we can compare against them
-    // during the next run ...
-    last_flats = flats.to_vec();
+    // ... remember them and persist them so we still know them after a
+    // restart, unless this is just a dry run ...
+    if !dry_run && enqueued {
+      for flat in filtered_flats.iter() {
+        guarded_dedup.write().unwrap().remember(flat);
+        if let Err(e) = store.remember(flat) {
+          eprintln!("could not remember flat: {}", e.message);
+        }
+      }
+      if let Err(e) = store.flush() {
+        eprintln!("could not persist flats: {}", e.message);
+      }
+    }
 
-    // pause for 5 minutes
-    std::thread::sleep(std::time::Duration::from_secs(300));
+    // ... and pass them on, along with those that could not be delivered
+    // before
+    if !app_config.test && !dry_run {
+      deliver(&mut sinks, &store);
+    }
+    if once {
+      break;
+    }
+  }
+  if shutdown.is_requested() {
+    println!("shut down gracefully.");
   }
 }
 
-fn run_thread(
-  guarded_configs: Arc<Mutex<Vec<Config>>>,
-  thread_number: usize,
-  conf: &ApplicationConfig,
-) -> Vec<Flat> {
-  let mut flats: Vec<Flat> = vec![];
-  loop {
-    let config_opt = guarded_configs.lock().unwrap().pop();
-    match config_opt {
-      Some(config) => {
-        flats.append(&mut process_config(&conf, &config, thread_number));
+/// Prints the flats of the target, whether they are known or not.
+fn crawl(app_config: &ApplicationConfig, args: &ArgMatches) {
+  let id = args.value_of("target").unwrap_or_default();
+  let target = match app_config.targets.iter().find(|target| target.id() == id) {
+    Some(target) => target.clone(),
+    None => {
+      eprintln!("there is no target '{}', see list-targets.", id);
+      process::exit(1);
+    }
+  };
+  let shutdown = Shutdown::new();
+  if let Err(e) = shutdown.on_signals() {
+    eprintln!("could not handle signals: {}", e.message);
+    process::exit(1);
+  }
+  shutdown.cancel_after(Duration::from_secs(0));
+  shutdown.exit_after(app_config.exit_timeout);
+
+  let is_known: IsKnown = Arc::new(|_| false);
+  let target_results = create_runtime()
+    .block_on(pipeline::crawl(
+      create_http_or_exit(app_config),
+      vec![target],
+      1,
+      is_known,
+      Running::default(),
+      shutdown,
+    ))
+    .unwrap_or_default();
+  for TargetResult { target, result } in target_results {
+    match result {
+      Ok(result) => {
+        for flat in result.flats.iter() {
+          match serde_json::to_string(flat) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("could not serialize flat: {}", e),
+          }
+        }
+        eprintln!(
+          "found {} flats on {} pages of '{}' with {} requests.",
+          result.flats.len(),
+          result.pages,
+          target.id(),
+          result.attempts
+        );
+      }
+      Err(e) => {
+        eprintln!("could not crawl '{}': {}", target.id(), e.message);
+        process::exit(1);
+      }
+    }
+  }
+}
+
+fn list_targets(app_config: &ApplicationConfig) {
+  for target in app_config.targets.iter() {
+    println!(
+      "{}\t{}\t{}\tevery {} seconds",
+      target.id(),
+      target.crawler.name(),
+      target.city,
+      target.schedule.interval.as_secs()
+    );
+  }
+}
+
+fn geocode_address(app_config: &ApplicationConfig, args: &ArgMatches) {
+  let address = args
+    .values_of("address")
+    .map(|words| words.collect::<Vec<_>>().join(" "))
+    .unwrap_or_default();
+  let preferred_area = match args.value_of("city") {
+    Some(slug) => match app_config.cities.find(slug) {
+      Some(info) => Some(&info.bounding_box),
+      None => {
+        eprintln!("there is no city '{}'.", slug);
+        process::exit(1);
       }
-      None => break,
+    },
+    None => None,
+  };
+  match geocode::geocode(
+    &create_http_or_exit(app_config),
+    &app_config.request,
+    &app_config.nominatim_url,
+    &address,
+    preferred_area,
+    &Cancellation::new(),
+  ) {
+    Ok(result) => println!(
+      "{}, {} (uncertainty: {} m, district: {})",
+      result.coord.latitude,
+      result.coord.longitude,
+      result.uncertainty,
+      result.district.as_deref().unwrap_or("unknown")
+    ),
+    Err(e) => {
+      eprintln!("could not geocode '{}': {}", address, e.message);
+      process::exit(1);
     }
   }
-  flats
 }
 
-fn geocode_flats(results: &Vec<Flat>, config: &ApplicationConfig) -> Vec<Flat> {
+/// Locates the flats, unless the crawler has to stop right away, in which
+/// case the remaining flats are passed on as they are.
+fn geocode_flats(
+  results: &Vec<Flat>,
+  config: &ApplicationConfig,
+  http: &Http,
+  cancellation: &Cancellation,
+) -> Vec<Flat> {
   let mut enriched_flats = Vec::new();
   for flat in results {
     let geocode_result_opt = match &flat.data {
-      Some(data) => match geocode::geocode(&config.nominatim_url, &data.address) {
-        Ok(coords) => Some(coords),
-        Err(_) => None,
-      },
-      None => None,
+      Some(data) if !cancellation.is_cancelled() => geocode::geocode(
+        http,
+        &config.request,
+        &config.nominatim_url,
+        &data.address,
+        config.cities.get(&flat.city).map(|info| &info.bounding_box),
+        cancellation,
+      )
+      .ok(),
+      _ => None,
     };
     let enriched_flat = match geocode_result_opt {
-      Some(geocode_result) => flat.locate(&geocode_result.coord, geocode_result.uncertainty),
+      Some(geocode_result) => flat.locate(&geocode_result),
       None => flat.clone(),
     };
     enriched_flats.push(enriched_flat);
@@ -166,92 +538,117 @@ fn geocode_flats(results: &Vec<Flat>, config: &ApplicationConfig) -> Vec<Flat> {
   enriched_flats
 }
 
-fn process_config(
+/// Collects the flats of all targets along with those that have not been
+/// seen before, which also decide how often each target will be crawled.
+fn collect_flats(
+  target_results: Vec<TargetResult>,
   app_config: &ApplicationConfig,
-  crawl_config: &Config,
-  thread_number: usize,
-) -> Vec<Flat> {
-  let crawler = crawlers::get_crawler(&crawl_config.crawler);
-  match crawler {
-    Ok(crawler) => {
-      println!(
-        "processing '{}' on thread {} ...",
-        crawler.name(),
-        thread_number
-      );
-      let flats_result = crawlers::execute(crawl_config, &crawler);
-      if flats_result.is_ok() {
-        let flats = flats_result.unwrap();
+  dedup: &Dedup,
+  scheduler: &mut Scheduler,
+  init_run: bool,
+) -> (Vec<Flat>, Vec<Flat>) {
+  let mut flats = Vec::new();
+  let mut new_flats = Vec::new();
+  // the flats are only remembered once they are in the outboxes, until then
+  // this keeps a flat that several targets have found from counting twice
+  let mut batch = Dedup::new();
+  for TargetResult { target, result } in target_results {
+    match result {
+      Ok(result) => {
+        println!(
+          "found {} flats on {} pages of '{}{}' with {} requests.",
+          result.flats.len(),
+          result.pages,
+          target.host,
+          target.path,
+          result.attempts
+        );
         if app_config.test {
-          for ref flat in &flats {
+          for ref flat in &result.flats {
             println!("parsed flat: {:?}", flat);
           }
         }
-        flats
-      } else {
-        eprintln!("error: {:?}", flats_result.err().unwrap().message);
-        vec![]
+        // filter results for flats that we have seen before
+        let previously_new = new_flats.len();
+        for flat in result.flats.iter() {
+          if dedup.is_new(flat) && batch.is_new(flat) {
+            batch.remember(flat);
+            new_flats.push(flat.clone());
+          }
+        }
+        // during the initial run, all flats are new
+        if init_run {
+          scheduler.reschedule(&target, Instant::now());
+        } else {
+          scheduler.completed(&target, Instant::now(), new_flats.len() - previously_new);
+        }
+        flats.extend(result.flats);
+      }
+      Err(e) => {
+        eprintln!(
+          "could not crawl '{}{}': {}",
+          target.host, target.path, e.message
+        );
+        scheduler.reschedule(&target, Instant::now());
       }
-    }
-    Err(e) => {
-      eprintln!("config could not be processed: {:?}", e.message);
-      vec![]
     }
   }
+  (flats, new_flats)
 }
 
-fn send_results(app_config: &ApplicationConfig, host: &str, results: Vec<Flat>) {
-  let exchange = if app_config.test {
-    "test_flats_exchange"
-  } else {
-    "flats_exchange"
-  };
-  let mut address = String::from("amqp://");
-  address.push_str(app_config.amqp_config.username.as_str());
-  address.push_str(":");
-  address.push_str(app_config.amqp_config.password.as_str());
-  address.push_str("@");
-  address.push_str(host);
-  address.push_str(":5672/%2f");
-
-  let connection = Client::connect(address.as_str(), ConnectionProperties::default())
-    .wait()
-    .expect("connection error");
-
-  let channel = connection
-    .create_channel()
-    .wait()
-    .expect("channel could not be created");
-
-  println!("connection successfully established");
-
-  channel
-    .exchange_declare(
-      exchange,
-      ExchangeKind::Fanout,
-      ExchangeDeclareOptions::default(),
-      FieldTable::default(),
-    )
-    .wait()
-    .expect("could not create exchange");
-
-  println!("exchange successfully created");
-
-  print!("sending flats ");
-  for flat in results {
-    channel
-      .basic_publish(
-        exchange,
-        &format!("flats_{:?}", flat.city),
-        serde_json::to_string(&flat).unwrap().as_bytes().to_vec(),
-        BasicPublishOptions::default(),
-        BasicProperties::default(),
-      )
-      .wait()
-      .expect("could not send flat!");
-    print!(".");
+/// Sends the flats that are waiting in the outbox of every sink. Only the
+/// flats that a sink has taken are removed, the others are tried again in
+/// the next cycle.
+fn deliver(sinks: &mut [(String, Box<dyn Sink>)], store: &Store) {
+  for (id, sink) in sinks.iter_mut() {
+    let name = sink.name();
+    let pending = match store.pending(id) {
+      Ok(pending) => pending,
+      Err(e) => {
+        eprintln!("could not read the outbox of {}: {}", name, e.message);
+        continue;
+      }
+    };
+    if pending.is_empty() {
+      continue;
+    }
+    match sink.send_some(&pending) {
+      Ok(()) => {
+        println!("sent {} flats to {}.", pending.len(), name);
+        if let Err(e) = store.delivered(id, &pending) {
+          eprintln!("could not empty the outbox of {}: {}", name, e.message);
+        }
+      }
+      Err(undelivered) => {
+        let taken: Vec<Flat> = undelivered
+          .taken
+          .iter()
+          .map(|&index| pending[index].clone())
+          .collect();
+        if !taken.is_empty() {
+          println!("sent {} flats to {}.", taken.len(), name);
+          if let Err(e) = store.delivered(id, &taken) {
+            eprintln!("could not empty the outbox of {}: {}", name, e.message);
+          }
+        }
+        eprintln!(
+          "could not send flats to {}, {} flats stay in its outbox: {}",
+          name,
+          pending.len() - taken.len(),
+          undelivered.error.message
+        );
+      }
+    }
   }
-  println!();
+}
 
-  println!("sending flats complete.");
+/// Passes the flats on to every sink, a failing sink does not keep the
+/// others from getting them.
+fn send_results(sinks: &mut [(String, Box<dyn Sink>)], flats: &[Flat]) {
+  for (_, sink) in sinks.iter_mut() {
+    match sink.send(flats) {
+      Ok(()) => println!("sent {} flats to {}.", flats.len(), sink.name()),
+      Err(e) => eprintln!("could not send flats to {}: {}", sink.name(), e.message),
+    }
+  }
 }
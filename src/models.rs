@@ -2,8 +2,11 @@ mod city;
 mod encodings;
 mod flat;
 
+pub use self::city::Cities;
 pub use self::city::City;
+pub use self::city::CityInfo;
 pub use self::encodings::Encoding;
 pub use self::flat::Flat;
 pub use self::flat::FlatData;
 pub use self::flat::Location;
+pub use self::flat::SCHEMA_VERSION;
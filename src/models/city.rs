@@ -1,9 +1,155 @@
+use crate::geocode::BoundingBox;
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
+use std::fmt;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-pub enum City {
-  Munich,
-  Wuerzburg,
-  Augsburg,
-  Kempten,
+/// Identifies a city by its slug.
+///
+/// The slug is what gets serialized into the `city` field of a flat and is
+/// also used to build the routing keys.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(transparent)]
+pub struct City(String);
+
+impl City {
+  pub fn new(slug: &str) -> City {
+    City(slug.to_owned())
+  }
+
+  pub fn slug(&self) -> &str {
+    &self.0
+  }
+}
+
+impl fmt::Display for City {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CityInfo {
+  pub slug: String,
+  pub name: String,
+  pub country: String,
+  pub bounding_box: BoundingBox,
+}
+
+impl CityInfo {
+  pub fn city(&self) -> City {
+    City::new(&self.slug)
+  }
+}
+
+/// The catalog of all cities that targets can be configured for.
+#[derive(Debug, Clone)]
+pub struct Cities {
+  cities: Vec<CityInfo>,
+}
+
+impl Default for Cities {
+  fn default() -> Cities {
+    Cities {
+      cities: vec![
+        city_info("Munich", "München", 48.0616, 48.2481, 11.3607, 11.7229),
+        city_info("Wuerzburg", "Würzburg", 49.7100, 49.8450, 9.8710, 10.0143),
+        city_info("Augsburg", "Augsburg", 48.2581, 48.4586, 10.7633, 10.9593),
+        city_info(
+          "Kempten",
+          "Kempten (Allgäu)",
+          47.6740,
+          47.7790,
+          10.2420,
+          10.3700,
+        ),
+      ],
+    }
+  }
+}
+
+impl Cities {
+  /// Adds the city to the catalog or replaces the one with the same slug.
+  pub fn add(&mut self, info: CityInfo) {
+    match self
+      .cities
+      .iter_mut()
+      .find(|existing| existing.slug.eq_ignore_ascii_case(&info.slug))
+    {
+      Some(existing) => *existing = info,
+      None => self.cities.push(info),
+    }
+  }
+
+  pub fn find(&self, slug: &str) -> Option<&CityInfo> {
+    self
+      .cities
+      .iter()
+      .find(|info| info.slug.eq_ignore_ascii_case(slug))
+  }
+
+  pub fn get(&self, city: &City) -> Option<&CityInfo> {
+    self.find(city.slug())
+  }
+
+  pub fn slugs(&self) -> Vec<&str> {
+    self.cities.iter().map(|info| info.slug.as_str()).collect()
+  }
+}
+
+fn city_info(
+  slug: &str,
+  name: &str,
+  min_lat: f32,
+  max_lat: f32,
+  min_lon: f32,
+  max_lon: f32,
+) -> CityInfo {
+  CityInfo {
+    slug: slug.to_owned(),
+    name: name.to_owned(),
+    country: "DE".to_owned(),
+    bounding_box: BoundingBox {
+      min_lat,
+      max_lat,
+      min_lon,
+      max_lon,
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Cities, City, CityInfo};
+  use crate::geocode::BoundingBox;
+
+  #[test]
+  fn serializes_to_slug() {
+    let city = City::new("Munich");
+    assert_eq!(serde_json::to_string(&city).unwrap(), "\"Munich\"");
+    assert_eq!(
+      serde_json::from_str::<City>("\"Kempten\"").unwrap(),
+      City::new("Kempten")
+    );
+  }
+
+  #[test]
+  fn finds_cities_by_slug() {
+    let mut cities = Cities::default();
+    assert_eq!(cities.find("munich").unwrap().city(), City::new("Munich"));
+    assert!(cities.find("Berlin").is_none());
+
+    cities.add(CityInfo {
+      slug: "Berlin".to_owned(),
+      name: "Berlin".to_owned(),
+      country: "DE".to_owned(),
+      bounding_box: BoundingBox {
+        min_lat: 52.3383,
+        max_lat: 52.6755,
+        min_lon: 13.0884,
+        max_lon: 13.7611,
+      },
+    });
+    assert_eq!(cities.find("Berlin").unwrap().name, "Berlin");
+    assert_eq!(cities.slugs().len(), 5);
+  }
 }
@@ -1,7 +1,142 @@
-use serde_derive::{Deserialize, Serialize};
+extern crate encoding_rs;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub enum Encoding {
-    Latin1,
-    Utf8,
+/// A character encoding, identified by any label that `encoding_rs` knows
+/// (e.g. "utf-8", "latin1", "windows-1252" or "iso-8859-15").
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Encoding(&'static encoding_rs::Encoding);
+
+impl Encoding {
+  pub fn from_name(name: &str) -> Option<Encoding> {
+    encoding_rs::Encoding::for_label(name.trim().as_bytes()).map(Encoding)
+  }
+
+  pub fn name(&self) -> &'static str {
+    self.0.name()
+  }
+
+  /// Finds out how a document is encoded. A byte order mark wins over the
+  /// charset of the Content-Type header, which wins over a `<meta>` tag.
+  /// Documents without any of those are decoded with the fallback or UTF-8.
+  pub fn detect(content_type: Option<&str>, body: &[u8], fallback: Option<Encoding>) -> Encoding {
+    if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(body) {
+      return Encoding(encoding);
+    }
+    content_type
+      .and_then(Self::from_content_type)
+      .or_else(|| Self::from_meta(body))
+      .or(fallback)
+      .unwrap_or(Encoding(encoding_rs::UTF_8))
+  }
+
+  fn from_content_type(content_type: &str) -> Option<Encoding> {
+    content_type
+      .split(';')
+      .filter_map(|parameter| {
+        let mut pair = parameter.splitn(2, '=');
+        match (pair.next(), pair.next()) {
+          (Some(key), Some(value)) if key.trim().eq_ignore_ascii_case("charset") => {
+            Some(value.trim().trim_matches(['"', '\'']))
+          }
+          _ => None,
+        }
+      })
+      .next()
+      .and_then(Self::from_name)
+  }
+
+  /// Looks for `<meta charset="...">` or `<meta http-equiv="Content-Type"
+  /// content="...; charset=...">` within the first 1024 bytes.
+  fn from_meta(body: &[u8]) -> Option<Encoding> {
+    let head = String::from_utf8_lossy(&body[..body.len().min(1024)]).to_lowercase();
+    let encoding = head
+      .split("<meta")
+      .skip(1)
+      .filter_map(|tag| {
+        let tag = tag.split('>').next()?;
+        let value = tag[tag.find("charset")? + "charset".len()..]
+          .trim_start()
+          .strip_prefix('=')?
+          .trim_start()
+          .trim_start_matches(['"', '\'']);
+        let label: String = value
+          .chars()
+          .take_while(|c| c.is_ascii_alphanumeric() || "-_:.".contains(*c))
+          .collect();
+        Self::from_name(&label)
+      })
+      .next()?;
+    // a document that could be read as ASCII to find the tag cannot be UTF-16
+    if encoding.0 == encoding_rs::UTF_16LE || encoding.0 == encoding_rs::UTF_16BE {
+      Some(Encoding(encoding_rs::UTF_8))
+    } else {
+      Some(encoding)
+    }
+  }
+
+  pub fn decode(&self, bytes: &[u8]) -> String {
+    let (decoded, _, _) = self.0.decode(bytes);
+    decoded.into_owned()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Encoding;
+
+  #[test]
+  fn finds_encodings_by_label() {
+    assert_eq!(Encoding::from_name("utf8").unwrap().name(), "UTF-8");
+    assert_eq!(
+      Encoding::from_name("latin1").unwrap().name(),
+      "windows-1252"
+    );
+    assert_eq!(
+      Encoding::from_name("ISO-8859-15").unwrap().name(),
+      "ISO-8859-15"
+    );
+    assert!(Encoding::from_name("klingon").is_none());
+  }
+
+  #[test]
+  fn detects_encodings() {
+    let latin1 = Encoding::from_name("latin1");
+    let meta = b"<html><head><meta charset=\"iso-8859-15\"></head></html>";
+    let http_equiv =
+      b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1252\">";
+
+    assert_eq!(
+      Encoding::detect(None, b"\xEF\xBB\xBFhello", latin1).name(),
+      "UTF-8"
+    );
+    assert_eq!(
+      Encoding::detect(Some("text/html; charset=\"UTF-8\""), meta, latin1).name(),
+      "UTF-8"
+    );
+    assert_eq!(
+      Encoding::detect(Some("text/html"), meta, None).name(),
+      "ISO-8859-15"
+    );
+    assert_eq!(
+      Encoding::detect(None, http_equiv, None).name(),
+      "windows-1252"
+    );
+    assert_eq!(
+      Encoding::detect(None, b"<html></html>", latin1).name(),
+      "windows-1252"
+    );
+    assert_eq!(
+      Encoding::detect(None, b"<html></html>", None).name(),
+      "UTF-8"
+    );
+  }
+
+  #[test]
+  fn decodes_german_characters() {
+    let latin1 = Encoding::from_name("latin1").unwrap();
+
+    assert_eq!(
+      latin1.decode(b"Gr\xF6\xDFe: 3 Zimmer \x80"),
+      "Größe: 3 Zimmer €"
+    );
+  }
 }
@@ -1,26 +1,41 @@
-use crate::geocode::Coordinate;
+use crate::geocode::GeocodeResult;
 use crate::models::city::City;
 use chrono::prelude::*;
-use regex::Regex;
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// The version of the JSON that flats are passed on as. It has to be raised
+/// whenever a field is renamed, removed or changes its meaning, so that
+/// consumers can tell the versions apart.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Where the flat is, as found by geocoding its address.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Location {
   pub latitude: f32,
   pub longitude: f32,
+  /// in meters
   pub uncertainty: f32,
+  /// the district of the city, as far as it is known
+  #[serde(default)]
+  pub district: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A flat as it has been found on one of the sources.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Flat {
+  /// the crawler that has found the flat
   pub source: String,
+  /// when the flat has been found, in seconds since the epoch
   pub date: i64,
+  /// the slug of the city
   pub city: City,
   pub data: Option<FlatData>,
   pub location: Option<Location>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct FlatData {
   pub rent: f32,
   pub squaremeters: f32,
@@ -28,6 +43,30 @@ pub struct FlatData {
   pub title: String,
   pub externalid: String,
   pub rooms: f32,
+  // the following details are only known for some of the sources,
+  // many of them will only be listed on the detail page
+  #[serde(default)]
+  pub warm_rent: Option<f32>,
+  #[serde(default)]
+  pub additional_costs: Option<f32>,
+  #[serde(default)]
+  pub deposit: Option<f32>,
+  #[serde(default)]
+  pub floor: Option<i32>,
+  #[serde(default)]
+  pub available_from: Option<String>,
+  #[serde(default)]
+  pub furnished: Option<bool>,
+  #[serde(default)]
+  pub balcony: Option<bool>,
+  #[serde(default)]
+  pub kitchen: Option<bool>,
+  #[serde(default)]
+  pub url: Option<String>,
+  #[serde(default)]
+  pub description: Option<String>,
+  #[serde(default)]
+  pub images: Vec<String>,
 }
 
 impl PartialEq for Flat {
@@ -43,18 +82,44 @@ impl PartialEq for Flat {
 
 impl Flat {
   fn is_equal_to(&self, other: &Self) -> bool {
-    let special_characters_regex = Regex::new("[^0-9a-zA-Z]+").unwrap();
     self.city == other.city
-      && match (&self.data, &other.data) {
-        (None, None) => false,
-        (Some(ref d1), Some(ref d2)) => {
-          special_characters_regex.replace_all(&d1.title.to_lowercase(), "")
-            == special_characters_regex.replace_all(&d2.title.to_lowercase(), "")
-        }
+      && match (self.normalized_title(), other.normalized_title()) {
+        (Some(t1), Some(t2)) => t1 == t2,
         _ => false,
       }
   }
 
+  /// The lowercased title without any special characters.
+  pub fn normalized_title(&self) -> Option<String> {
+    self.data.as_ref().map(|data| {
+      data
+        .title
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect()
+    })
+  }
+
+  /// Identifies the flat across crawls, as long as its source keeps the
+  /// external id.
+  pub fn id(&self) -> Option<String> {
+    self
+      .data
+      .as_ref()
+      .map(|data| format!("{}/{}/{}", self.city, self.source, data.externalid))
+  }
+
+  /// The JSON Schema of flats, which names its `schema_version`.
+  pub fn json_schema() -> RootSchema {
+    let mut schema = schema_for!(Flat);
+    schema
+      .schema
+      .extensions
+      .insert("schema_version".to_owned(), SCHEMA_VERSION.into());
+    schema
+  }
+
   pub fn new(source: String, city: City) -> Flat {
     Flat {
       date: Utc::now().timestamp(),
@@ -75,16 +140,17 @@ impl Flat {
     }
   }
 
-  pub fn locate(&self, coord: &Coordinate, uncertainty: f32) -> Flat {
+  pub fn locate(&self, result: &GeocodeResult) -> Flat {
     Flat {
       city: self.city.clone(),
       source: self.source.to_owned(),
       date: self.date,
       data: self.data.clone(),
       location: Some(Location {
-        latitude: coord.latitude,
-        longitude: coord.longitude,
-        uncertainty,
+        latitude: result.coord.latitude,
+        longitude: result.coord.longitude,
+        uncertainty: result.uncertainty,
+        district: result.district.clone(),
       }),
     }
   }
@@ -100,7 +166,7 @@ mod tests {
   fn compare_flat_too_simple() {
     let flat_a = Flat {
       source: String::from("some source A"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: None,
       location: None,
@@ -108,7 +174,7 @@ mod tests {
 
     let flat_b = Flat {
       source: String::from("some source A"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: None,
       location: None,
@@ -121,7 +187,7 @@ mod tests {
   fn compare_flat_simple() {
     let flat_a = Flat {
       source: String::from("some source A"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: Some(FlatData {
         rent: 100.,
@@ -130,13 +196,14 @@ mod tests {
         title: String::from("This is some title"),
         externalid: String::from("1"),
         rooms: 3.,
+        ..Default::default()
       }),
       location: None,
     };
 
     let flat_b = Flat {
       source: String::from("some source A"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: Some(FlatData {
         rent: 100.,
@@ -145,6 +212,7 @@ mod tests {
         title: String::from("This is some other title"),
         externalid: String::from("1"),
         rooms: 1.,
+        ..Default::default()
       }),
       location: None,
     };
@@ -156,7 +224,7 @@ mod tests {
   fn compare_flat_complex() {
     let flat_a = Flat {
       source: String::from("some source A"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: Some(FlatData {
         rent: 100.,
@@ -165,13 +233,14 @@ mod tests {
         title: String::from("This is some title"),
         externalid: String::from("1a"),
         rooms: 3.,
+        ..Default::default()
       }),
       location: None,
     };
 
     let flat_b = Flat {
       source: String::from("some source B"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: Some(FlatData {
         rent: 100.,
@@ -180,6 +249,7 @@ mod tests {
         title: String::from("This is some title"),
         externalid: String::from("1b"),
         rooms: 3.,
+        ..Default::default()
       }),
       location: None,
     };
@@ -191,7 +261,7 @@ mod tests {
   fn compare_flat_complex_special_chars() {
     let flat_a = Flat {
       source: String::from("some source A"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: Some(FlatData {
         rent: 100.,
@@ -200,13 +270,14 @@ mod tests {
         title: String::from("This is% some title!"),
         externalid: String::from("1a"),
         rooms: 3.,
+        ..Default::default()
       }),
       location: None,
     };
 
     let flat_b = Flat {
       source: String::from("some source B"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: Some(FlatData {
         rent: 101.,
@@ -215,6 +286,7 @@ mod tests {
         title: String::from("This is some title"),
         externalid: String::from("1b"),
         rooms: 3.5,
+        ..Default::default()
       }),
       location: None,
     };
@@ -226,7 +298,7 @@ mod tests {
   fn compare_flat_not_equal() {
     let flat_a = Flat {
       source: String::from("some source A"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: None,
       location: None,
@@ -234,7 +306,7 @@ mod tests {
 
     let flat_b = Flat {
       source: String::from("some source B"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: None,
       location: None,
@@ -247,7 +319,7 @@ mod tests {
   fn compare_flat_complex_not_equal() {
     let flat_a = Flat {
       source: String::from("some source A"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: Some(FlatData {
         rent: 100.,
@@ -256,13 +328,14 @@ mod tests {
         title: String::from("This is% some title!"),
         externalid: String::from("1a"),
         rooms: 3.,
+        ..Default::default()
       }),
       location: None,
     };
 
     let flat_b = Flat {
       source: String::from("some source B"),
-      city: City::Munich,
+      city: City::new("Munich"),
       date: 0,
       data: Some(FlatData {
         rent: 101.,
@@ -271,10 +344,54 @@ mod tests {
         title: String::from("This is some other title"),
         externalid: String::from("1b"),
         rooms: 3.5,
+        ..Default::default()
       }),
       location: None,
     };
 
     assert_ne!(flat_a, flat_b);
   }
+
+  #[test]
+  fn deserialize_flat_without_details() {
+    let flat: Flat = serde_json::from_str(
+      r#"{
+        "source": "immoscout",
+        "date": 0,
+        "city": "Munich",
+        "data": {
+          "rent": 100.0,
+          "squaremeters": 60.0,
+          "address": "Some address",
+          "title": "Some title",
+          "externalid": "1",
+          "rooms": 2.0
+        },
+        "location": null
+      }"#,
+    )
+    .unwrap();
+    let data = flat.data.unwrap();
+
+    assert_eq!(flat.city, City::new("Munich"));
+    assert_eq!(data.externalid, "1");
+    assert_eq!(data.warm_rent, None);
+    assert_eq!(data.deposit, None);
+    assert_eq!(data.floor, None);
+    assert_eq!(data.balcony, None);
+    assert_eq!(data.url, None);
+    assert!(data.images.is_empty());
+  }
+
+  #[test]
+  fn publishes_the_current_schema() {
+    let published: serde_json::Value =
+      serde_json::from_str(include_str!("../../schema/flat.schema.json")).unwrap();
+
+    // run `cargo run -- schema > schema/flat.schema.json` after changing flats
+    assert_eq!(
+      published,
+      serde_json::to_value(Flat::json_schema()).unwrap()
+    );
+  }
 }
@@ -0,0 +1,324 @@
+extern crate futures;
+extern crate tokio;
+extern crate tokio_threadpool;
+
+use crate::crawlers::{self, Config, CrawlResult};
+use crate::http::Http;
+use crate::models::Flat;
+use crate::shutdown::Shutdown;
+use futures::future::{self, Future};
+use futures::stream::{self, Stream};
+use futures::sync::oneshot;
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use tokio::timer::Timeout;
+
+#[derive(Debug)]
+pub struct Error {
+  pub message: String,
+}
+
+/// Tells whether a flat has been seen before, shared by all running crawls.
+pub type IsKnown = Arc<dyn Fn(&Flat) -> bool + Send + Sync>;
+
+/// The ids of the targets that are being crawled, a crawl that has timed out
+/// stays in here until its worker has actually stopped.
+pub type Running = Arc<Mutex<HashSet<String>>>;
+
+/// Takes a target out of `Running` once its crawl has stopped.
+struct RunningGuard {
+  running: Running,
+  id: String,
+}
+
+impl Drop for RunningGuard {
+  fn drop(&mut self) {
+    self.running.lock().unwrap().remove(&self.id);
+  }
+}
+
+/// The outcome of crawling a single target.
+#[derive(Debug)]
+pub struct TargetResult {
+  pub target: Config,
+  pub result: Result<CrawlResult, Error>,
+}
+
+/// Crawls all targets with at most `concurrency` of them at the same time.
+///
+/// The crawlers parse their pages synchronously, so every crawl runs on a
+/// blocking section of the tokio threadpool. A crawl that exceeds the
+/// timeout of its target is cancelled, just like all crawls once the grace
+/// period of a `shutdown` has passed. No crawls are started anymore after a
+/// shutdown has been requested. Neither a timeout nor a panicking crawler
+/// keeps the other targets from finishing. A target that is still `running`
+/// from an earlier call, e.g. because its crawl timed out in the middle of a
+/// request, is skipped.
+pub fn crawl(
+  http: Arc<Http>,
+  targets: Vec<Config>,
+  concurrency: usize,
+  is_known: IsKnown,
+  running: Running,
+  shutdown: Shutdown,
+) -> impl Future<Item = Vec<TargetResult>, Error = ()> {
+  stream::iter_ok(targets)
+    .map(move |target| {
+      crawl_target(
+        http.clone(),
+        target,
+        is_known.clone(),
+        running.clone(),
+        shutdown.clone(),
+      )
+    })
+    .buffer_unordered(concurrency.max(1))
+    .collect()
+}
+
+fn crawl_target(
+  http: Arc<Http>,
+  target: Config,
+  is_known: IsKnown,
+  running: Running,
+  shutdown: Shutdown,
+) -> impl Future<Item = TargetResult, Error = ()> {
+  let target_cancellation = shutdown.cancellation().child();
+  let crawl_cancellation = target_cancellation.clone();
+  let crawl_target = target.clone();
+  let mut crawl = Some(move || {
+    if shutdown.is_requested() {
+      return Err(Error {
+        message: "crawl has not been started, as the crawler is shutting down".to_owned(),
+      });
+    }
+    if crawl_cancellation.is_cancelled() {
+      return Err(Error {
+        message: "crawl has been cancelled before it started".to_owned(),
+      });
+    }
+    let id = crawl_target.id();
+    if !running.lock().unwrap().insert(id.clone()) {
+      return Err(Error {
+        message: "the previous crawl of this target is still running".to_owned(),
+      });
+    }
+    let _guard = RunningGuard { running, id };
+    let crawler = crawlers::get_crawler(&crawl_target.crawler).map_err(|e| Error {
+      message: format!("config could not be processed: {}", e.message),
+    })?;
+    crawler.log("starting crawl ...".to_owned());
+    match panic::catch_unwind(AssertUnwindSafe(|| {
+      crawlers::execute(
+        &http,
+        &crawl_target,
+        crawler.as_ref(),
+        &*is_known,
+        &crawl_cancellation,
+      )
+    })) {
+      Ok(result) => result.map_err(|e| Error { message: e.message }),
+      Err(_) => Err(Error {
+        message: format!("crawler '{}' panicked", crawler.name()),
+      }),
+    }
+  });
+  // the crawl runs as a task of its own, so that it can be timed out while
+  // its blocking section is still running
+  let (sender, receiver) = oneshot::channel();
+  let blocking_crawl = future::poll_fn(move || {
+    tokio_threadpool::blocking(|| match crawl.take() {
+      Some(crawl) => crawl(),
+      None => Err(Error {
+        message: "crawl has already been run".to_owned(),
+      }),
+    })
+  })
+  .then(move |result| {
+    let result = result.unwrap_or_else(|e| {
+      Err(Error {
+        message: format!("crawl could not be run: {}", e),
+      })
+    });
+    // nobody is waiting for the result anymore, if the crawl timed out
+    let _ = sender.send(result);
+    Ok(())
+  });
+  let crawl_result = future::lazy(move || {
+    tokio::spawn(blocking_crawl);
+    receiver.map_err(|_| Error {
+      message: "crawl stopped unexpectedly".to_owned(),
+    })
+  });
+
+  let crawl_timeout = target.crawl_timeout;
+  Timeout::new(crawl_result.and_then(|result| result), crawl_timeout).then(move |result| {
+    let result = result.map_err(|e| {
+      if e.is_elapsed() {
+        target_cancellation.cancel();
+        Error {
+          message: format!(
+            "crawl has been cancelled after {} seconds",
+            crawl_timeout.as_secs()
+          ),
+        }
+      } else if e.is_timer() {
+        Error {
+          message: format!("crawl could not be timed: {}", e.into_timer().unwrap()),
+        }
+      } else {
+        e.into_inner().unwrap()
+      }
+    });
+    Ok(TargetResult { target, result })
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{crawl, IsKnown, Running};
+  use crate::crawlers::{Config, CrawlerImpl};
+  use crate::http::{Http, RequestSettings};
+  use crate::models::City;
+  use crate::shutdown::Shutdown;
+  use std::net::TcpListener;
+  use std::sync::Arc;
+  use std::thread;
+  use std::time::{Duration, Instant};
+  use tokio::runtime::Runtime;
+
+  fn target(host: String) -> Config {
+    Config {
+      host,
+      path: "/".to_owned(),
+      city: City::new("Munich"),
+      encoding: None,
+      crawler: CrawlerImpl::WGGesucht,
+      max_pages: 1,
+      stop_after_known: None,
+      details: false,
+      request: RequestSettings {
+        scheme: Some("http".to_owned()),
+        timeout: Some(3),
+        attempts: Some(1),
+        ..Default::default()
+      },
+      crawl_timeout: Duration::from_secs(1),
+      schedule: Default::default(),
+    }
+  }
+
+  #[test]
+  fn cancels_crawls_that_take_too_long() {
+    // accepts connections, but never answers
+    let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+    // nobody is listening on this port anymore
+    let closed = TcpListener::bind("127.0.0.1:0")
+      .unwrap()
+      .local_addr()
+      .unwrap()
+      .port();
+    let targets = vec![
+      target(format!("127.0.0.1:{}", silent.local_addr().unwrap().port())),
+      target(format!("localhost:{}", closed)),
+    ];
+    let http = Arc::new(Http::new(targets.iter().map(|t| &t.request), Default::default()).unwrap());
+    let is_known: IsKnown = Arc::new(|_| false);
+
+    let mut runtime = Runtime::new().unwrap();
+    let start = Instant::now();
+    let mut results = runtime
+      .block_on(crawl(
+        http,
+        targets,
+        2,
+        is_known,
+        Running::default(),
+        Shutdown::new(),
+      ))
+      .unwrap();
+    let duration = start.elapsed();
+    results.sort_by_key(|result| result.target.host.clone());
+
+    assert!(duration < Duration::from_secs(2));
+    assert_eq!(
+      results[0].result.as_ref().err().unwrap().message,
+      "crawl has been cancelled after 1 seconds"
+    );
+    assert!(results[1].result.is_err());
+  }
+
+  #[test]
+  fn starts_no_crawls_during_shutdown() {
+    let targets = vec![target("localhost:1".to_owned())];
+    let http = Arc::new(Http::new(targets.iter().map(|t| &t.request), Default::default()).unwrap());
+    let is_known: IsKnown = Arc::new(|_| false);
+    let shutdown = Shutdown::new();
+    shutdown.request();
+
+    let mut runtime = Runtime::new().unwrap();
+    let results = runtime
+      .block_on(crawl(
+        http,
+        targets,
+        1,
+        is_known,
+        Running::default(),
+        shutdown,
+      ))
+      .unwrap();
+
+    assert_eq!(
+      results[0].result.as_ref().err().unwrap().message,
+      "crawl has not been started, as the crawler is shutting down"
+    );
+  }
+
+  #[test]
+  fn skips_targets_whose_previous_crawl_still_runs() {
+    // accepts connections, but never answers
+    let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+    let targets = vec![target(format!(
+      "127.0.0.1:{}",
+      silent.local_addr().unwrap().port()
+    ))];
+    let http = Arc::new(Http::new(targets.iter().map(|t| &t.request), Default::default()).unwrap());
+    let is_known: IsKnown = Arc::new(|_| false);
+    let running = Running::default();
+
+    let mut runtime = Runtime::new().unwrap();
+    let first = runtime
+      .block_on(crawl(
+        http.clone(),
+        targets.clone(),
+        1,
+        is_known.clone(),
+        running.clone(),
+        Shutdown::new(),
+      ))
+      .unwrap();
+    // the request of the first crawl only times out after 3 seconds
+    let second = runtime
+      .block_on(crawl(
+        http,
+        targets,
+        1,
+        is_known,
+        running.clone(),
+        Shutdown::new(),
+      ))
+      .unwrap();
+
+    assert_eq!(
+      first[0].result.as_ref().err().unwrap().message,
+      "crawl has been cancelled after 1 seconds"
+    );
+    assert_eq!(
+      second[0].result.as_ref().err().unwrap().message,
+      "the previous crawl of this target is still running"
+    );
+    thread::sleep(Duration::from_secs(3));
+    assert!(running.lock().unwrap().is_empty());
+  }
+}
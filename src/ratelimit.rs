@@ -0,0 +1,238 @@
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+const DEFAULT_MIN_DELAY_IN_MILLISECONDS: u64 = 1000;
+const DEFAULT_MAX_CONCURRENT: u32 = 2;
+
+/// How politely a single host will be treated. Unset values fall back to the
+/// defaults of the `[rate_limit]` section.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RateLimit {
+  /// minimum delay in milliseconds between the start of two requests
+  pub min_delay: Option<u64>,
+  /// maximum number of requests that may be running at the same time
+  pub max_concurrent: Option<u32>,
+}
+
+impl RateLimit {
+  pub fn merge(&self, overrides: &RateLimit) -> RateLimit {
+    RateLimit {
+      min_delay: overrides.min_delay.or(self.min_delay),
+      max_concurrent: overrides.max_concurrent.or(self.max_concurrent),
+    }
+  }
+
+  pub fn min_delay(&self) -> Duration {
+    Duration::from_millis(self.min_delay.unwrap_or(DEFAULT_MIN_DELAY_IN_MILLISECONDS))
+  }
+
+  pub fn max_concurrent(&self) -> u32 {
+    self.max_concurrent.unwrap_or(DEFAULT_MAX_CONCURRENT)
+  }
+
+  pub fn validate(&self) -> Vec<String> {
+    if self.max_concurrent == Some(0) {
+      vec!["'max_concurrent' has to be at least 1".to_owned()]
+    } else {
+      vec![]
+    }
+  }
+}
+
+/// The limits of all hosts, hosts without limits of their own use the defaults.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimits {
+  pub defaults: RateLimit,
+  pub hosts: HashMap<String, RateLimit>,
+}
+
+impl RateLimits {
+  pub fn get(&self, host: &str) -> RateLimit {
+    match self.hosts.get(&host.to_lowercase()) {
+      Some(limit) => self.defaults.merge(limit),
+      None => self.defaults.clone(),
+    }
+  }
+}
+
+struct HostState {
+  running: u32,
+  next_request: Instant,
+}
+
+/// Keeps track of the requests to every host, so that all workers together
+/// stick to the limits of the hosts.
+pub struct RateLimiter {
+  limits: RwLock<RateLimits>,
+  hosts: Mutex<HashMap<String, HostState>>,
+  changed: Condvar,
+}
+
+/// Allows a single request, the host is free again once it has been dropped.
+pub struct Permit<'a> {
+  limiter: &'a RateLimiter,
+  host: String,
+}
+
+impl RateLimiter {
+  pub fn new(limits: RateLimits) -> RateLimiter {
+    RateLimiter {
+      limits: RwLock::new(limits),
+      hosts: Mutex::new(HashMap::new()),
+      changed: Condvar::new(),
+    }
+  }
+
+  /// Blocks until a request to the given host is allowed.
+  pub fn acquire(&self, host: &str) -> Permit<'_> {
+    let host = host.to_lowercase();
+    let mut hosts = self.hosts.lock().unwrap();
+    loop {
+      // the limits may have changed while waiting
+      let limit = self.limits.read().unwrap().get(&host);
+      let now = Instant::now();
+      let state = hosts.entry(host.clone()).or_insert(HostState {
+        running: 0,
+        next_request: now,
+      });
+      if state.running >= limit.max_concurrent() {
+        hosts = self.changed.wait(hosts).unwrap();
+      } else if state.next_request > now {
+        let delay = state.next_request - now;
+        hosts = self.changed.wait_timeout(hosts, delay).unwrap().0;
+      } else {
+        state.running += 1;
+        state.next_request = now + limit.min_delay();
+        return Permit {
+          limiter: self,
+          host,
+        };
+      }
+    }
+  }
+
+  /// Applies new limits, while the requests that are running and the time
+  /// of the last request to every host are kept.
+  pub fn set_limits(&self, limits: RateLimits) {
+    *self.limits.write().unwrap() = limits;
+    self.changed.notify_all();
+  }
+}
+
+impl<'a> Drop for Permit<'a> {
+  fn drop(&mut self) {
+    let mut hosts = self.limiter.hosts.lock().unwrap();
+    if let Some(state) = hosts.get_mut(&self.host) {
+      state.running -= 1;
+    }
+    self.limiter.changed.notify_all();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{RateLimit, RateLimiter, RateLimits};
+  use std::sync::Arc;
+  use std::thread;
+  use std::time::{Duration, Instant};
+
+  fn limiter(min_delay: u64, max_concurrent: u32) -> RateLimiter {
+    let mut limits = RateLimits::default();
+    limits.hosts.insert(
+      "www.immobilienscout24.de".to_owned(),
+      RateLimit {
+        min_delay: Some(min_delay),
+        max_concurrent: Some(max_concurrent),
+      },
+    );
+    RateLimiter::new(limits)
+  }
+
+  #[test]
+  fn keeps_a_minimum_delay() {
+    let limiter = limiter(50, 5);
+    let start = Instant::now();
+    for _ in 0..3 {
+      drop(limiter.acquire("www.immobilienscout24.de"));
+    }
+
+    assert!(start.elapsed() >= Duration::from_millis(100));
+  }
+
+  #[test]
+  fn limits_concurrent_requests() {
+    let limiter = Arc::new(limiter(0, 1));
+    let permit = limiter.acquire("WWW.IMMOBILIENSCOUT24.DE");
+    let inner_limiter = limiter.clone();
+    let handle = thread::spawn(move || {
+      let start = Instant::now();
+      drop(inner_limiter.acquire("www.immobilienscout24.de"));
+      start.elapsed()
+    });
+    thread::sleep(Duration::from_millis(50));
+    drop(permit);
+
+    assert!(handle.join().unwrap() >= Duration::from_millis(40));
+  }
+
+  #[test]
+  fn keeps_its_state_when_the_limits_change() {
+    let limiter = limiter(50, 5);
+    drop(limiter.acquire("www.immobilienscout24.de"));
+    let start = Instant::now();
+    limiter.set_limits(RateLimits::default());
+    drop(limiter.acquire("www.immobilienscout24.de"));
+
+    assert!(start.elapsed() >= Duration::from_millis(40));
+  }
+
+  #[test]
+  fn lets_waiting_requests_through_once_the_limits_are_raised() {
+    let limiter = Arc::new(limiter(0, 1));
+    let permit = limiter.acquire("www.immobilienscout24.de");
+    let inner_limiter = limiter.clone();
+    let handle = thread::spawn(move || {
+      let start = Instant::now();
+      drop(inner_limiter.acquire("www.immobilienscout24.de"));
+      start.elapsed()
+    });
+    thread::sleep(Duration::from_millis(50));
+    let mut limits = RateLimits::default();
+    limits.defaults.min_delay = Some(0);
+    limiter.set_limits(limits);
+    // without the new limits, the waiting request would only get through
+    // once the permit has been dropped
+    thread::sleep(Duration::from_secs(1));
+    drop(permit);
+
+    assert!(handle.join().unwrap() < Duration::from_millis(500));
+  }
+
+  #[test]
+  fn falls_back_to_defaults() {
+    let limits = RateLimits {
+      defaults: RateLimit {
+        min_delay: Some(200),
+        max_concurrent: None,
+      },
+      hosts: vec![(
+        "www.wg-gesucht.de".to_owned(),
+        RateLimit {
+          min_delay: None,
+          max_concurrent: Some(1),
+        },
+      )]
+      .into_iter()
+      .collect(),
+    };
+
+    assert_eq!(limits.get("www.wg-gesucht.de").max_concurrent(), 1);
+    assert_eq!(
+      limits.get("www.wg-gesucht.de").min_delay(),
+      Duration::from_millis(200)
+    );
+    assert_eq!(limits.get("www.immowelt.de").max_concurrent(), 2);
+  }
+}
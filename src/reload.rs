@@ -0,0 +1,84 @@
+extern crate signal_hook;
+
+use crate::shutdown::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Tells when the configuration should be read again, which is the case
+/// once one of its files has been created, modified or removed, or when the
+/// process has received SIGHUP.
+pub struct ConfigWatcher {
+  files: Vec<PathBuf>,
+  modified: Vec<Option<SystemTime>>,
+  requested: Arc<AtomicBool>,
+}
+
+impl ConfigWatcher {
+  /// Watches the given files, which do not have to exist yet.
+  pub fn new(files: Vec<PathBuf>) -> ConfigWatcher {
+    ConfigWatcher {
+      modified: modification_times(&files),
+      files,
+      requested: Default::default(),
+    }
+  }
+
+  pub fn on_signal(&self) -> Result<(), Error> {
+    signal_hook::flag::register(signal_hook::SIGHUP, self.requested.clone())?;
+    Ok(())
+  }
+
+  /// Whether the configuration has changed since the last time it has been
+  /// asked.
+  pub fn has_changed(&mut self) -> bool {
+    let requested = self.requested.swap(false, Ordering::SeqCst);
+    let modified = modification_times(&self.files);
+    if modified != self.modified {
+      self.modified = modified;
+      true
+    } else {
+      requested
+    }
+  }
+}
+
+fn modification_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+  files
+    .iter()
+    .map(|file| {
+      fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::ConfigWatcher;
+  use std::env;
+  use std::fs;
+  use std::sync::atomic::Ordering;
+
+  #[test]
+  fn notices_changed_files() {
+    let file = env::temp_dir().join("flatcrawl-reload-test.toml");
+    let _ = fs::remove_file(&file);
+    let mut watcher = ConfigWatcher::new(vec![file.clone()]);
+
+    assert!(!watcher.has_changed());
+    fs::write(&file, "test = true").unwrap();
+    assert!(watcher.has_changed());
+    assert!(!watcher.has_changed());
+
+    watcher.requested.store(true, Ordering::SeqCst);
+    assert!(watcher.has_changed());
+    assert!(!watcher.has_changed());
+
+    fs::remove_file(&file).unwrap();
+    assert!(watcher.has_changed());
+  }
+}
@@ -0,0 +1,483 @@
+extern crate chrono;
+extern crate rand;
+
+mod cron;
+
+pub use self::cron::Cron;
+
+use crate::crawlers::Config;
+use chrono::NaiveDateTime;
+use rand::Rng;
+use serde_derive::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+const DEFAULT_INTERVAL_IN_SECONDS: u64 = 300;
+const DEFAULT_JITTER_IN_SECONDS: u64 = 30;
+/// number of recent crawls whose new flats decide about the interval
+const RECENT_CRAWLS: usize = 5;
+
+/// When a target should be crawled, as it has been written down in the
+/// configuration. Unset values fall back to the schedule of the source and
+/// then to the global `[schedule]` section.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ScheduleSettings {
+  /// seconds between two crawls
+  pub interval: Option<u64>,
+  /// with a minimum or maximum, the interval adapts to how many new flats
+  /// the target has found recently, staying within those limits
+  pub min_interval: Option<u64>,
+  pub max_interval: Option<u64>,
+  /// up to this many seconds will be added to the interval at random
+  pub jitter: Option<u64>,
+  /// cron expressions of the times during which nothing will be crawled
+  pub quiet_hours: Option<Vec<String>>,
+}
+
+impl ScheduleSettings {
+  pub fn merge(&self, overrides: &ScheduleSettings) -> ScheduleSettings {
+    ScheduleSettings {
+      interval: overrides.interval.or(self.interval),
+      min_interval: overrides.min_interval.or(self.min_interval),
+      max_interval: overrides.max_interval.or(self.max_interval),
+      jitter: overrides.jitter.or(self.jitter),
+      quiet_hours: overrides
+        .quiet_hours
+        .clone()
+        .or_else(|| self.quiet_hours.clone()),
+    }
+  }
+
+  pub fn schedule(&self) -> Result<Schedule, Vec<String>> {
+    let mut errors = Vec::new();
+    let interval = self.interval.unwrap_or(DEFAULT_INTERVAL_IN_SECONDS);
+    let min_interval = self.min_interval.unwrap_or(interval);
+    let max_interval = self.max_interval.unwrap_or(interval);
+    if self.min_interval == Some(0) {
+      errors.push("'min_interval' has to be at least 1 second".to_owned());
+    } else if interval == 0 {
+      errors.push("'interval' has to be at least 1 second".to_owned());
+    } else if min_interval > interval || interval > max_interval {
+      errors.push(format!(
+        "'interval' ({}) has to be between 'min_interval' ({}) and 'max_interval' ({})",
+        interval, min_interval, max_interval
+      ));
+    }
+    let mut quiet_hours = Vec::new();
+    for expression in self.quiet_hours.iter().flatten() {
+      match Cron::parse(expression) {
+        Ok(cron) => quiet_hours.push(cron),
+        Err(e) => errors.push(e),
+      }
+    }
+    if errors.is_empty() {
+      Ok(Schedule {
+        interval: Duration::from_secs(interval),
+        min_interval: Duration::from_secs(min_interval),
+        max_interval: Duration::from_secs(max_interval),
+        jitter: Duration::from_secs(self.jitter.unwrap_or(DEFAULT_JITTER_IN_SECONDS)),
+        quiet_hours,
+      })
+    } else {
+      Err(errors)
+    }
+  }
+}
+
+/// When a target will be crawled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+  pub interval: Duration,
+  pub min_interval: Duration,
+  pub max_interval: Duration,
+  pub jitter: Duration,
+  pub quiet_hours: Vec<Cron>,
+}
+
+impl Default for Schedule {
+  fn default() -> Schedule {
+    ScheduleSettings::default().schedule().unwrap()
+  }
+}
+
+impl Schedule {
+  pub fn is_quiet(&self, time: &NaiveDateTime) -> bool {
+    self.quiet_hours.iter().any(|cron| cron.matches(time))
+  }
+
+  /// The interval plus some random jitter, so that the targets do not all
+  /// get crawled at the very same moment.
+  pub fn next_delay(&self, interval: Duration) -> Duration {
+    let jitter = self.jitter.as_millis() as u64;
+    interval + Duration::from_millis(rand::thread_rng().gen_range(0, jitter + 1))
+  }
+
+  /// Finds the interval that suits the number of new flats of recent crawls.
+  /// Targets that found more than one new flat per crawl get crawled twice
+  /// as often, targets that did not find any at all half as often again.
+  pub fn adapt(&self, interval: Duration, recent_new_flats: &VecDeque<usize>) -> Duration {
+    let total: usize = recent_new_flats.iter().sum();
+    let adapted = if total > recent_new_flats.len() {
+      interval / 2
+    } else if total == 0 && recent_new_flats.len() == RECENT_CRAWLS {
+      interval * 3 / 2
+    } else {
+      interval
+    };
+    adapted.max(self.min_interval).min(self.max_interval)
+  }
+}
+
+struct TargetState {
+  target: Config,
+  next_run: Instant,
+  interval: Duration,
+  recent_new_flats: VecDeque<usize>,
+}
+
+impl TargetState {
+  fn new(target: Config, next_run: Instant) -> TargetState {
+    TargetState {
+      next_run,
+      interval: target.schedule.interval,
+      recent_new_flats: VecDeque::with_capacity(RECENT_CRAWLS),
+      target,
+    }
+  }
+}
+
+/// Decides which targets are due to be crawled and how often.
+pub struct Scheduler {
+  targets: Vec<TargetState>,
+}
+
+impl Scheduler {
+  /// All targets will be due right away.
+  pub fn new(targets: Vec<Config>, now: Instant) -> Scheduler {
+    Scheduler {
+      targets: targets
+        .into_iter()
+        .map(|target| TargetState::new(target, now))
+        .collect(),
+    }
+  }
+
+  /// Takes over the targets of a new configuration. Targets that are kept
+  /// also keep their current interval, unless their schedule has changed.
+  /// New targets will be due right away.
+  pub fn update(&mut self, targets: Vec<Config>, now: Instant) {
+    let mut states: HashMap<String, TargetState> = self
+      .targets
+      .drain(..)
+      .map(|state| (state.target.id(), state))
+      .collect();
+    self.targets = targets
+      .into_iter()
+      .map(|target| match states.remove(&target.id()) {
+        Some(state) if state.target.schedule == target.schedule => TargetState { target, ..state },
+        Some(state) => {
+          let next_run = state.next_run.min(now + target.schedule.interval);
+          TargetState::new(target, next_run)
+        }
+        None => TargetState::new(target, now),
+      })
+      .collect();
+  }
+
+  /// The targets whose time has come and that are not within quiet hours.
+  pub fn due(&self, now: Instant, local_time: &NaiveDateTime) -> Vec<Config> {
+    self
+      .targets
+      .iter()
+      .filter(|state| state.next_run <= now && !state.target.schedule.is_quiet(local_time))
+      .map(|state| state.target.clone())
+      .collect()
+  }
+
+  /// Schedules the next crawl of the target after it has found the given
+  /// number of new flats, adapting its interval if that is allowed.
+  pub fn completed(&mut self, target: &Config, now: Instant, new_flats: usize) {
+    if let Some(state) = self.state(target) {
+      if state.recent_new_flats.len() == RECENT_CRAWLS {
+        state.recent_new_flats.pop_front();
+      }
+      state.recent_new_flats.push_back(new_flats);
+      let interval = state
+        .target
+        .schedule
+        .adapt(state.interval, &state.recent_new_flats);
+      if interval != state.interval {
+        println!(
+          "crawling '{}' every {} seconds from now on.",
+          target.id(),
+          interval.as_secs()
+        );
+        state.interval = interval;
+        // start over, so that the next change is based on the new interval
+        state.recent_new_flats.clear();
+      }
+      state.next_run = now + state.target.schedule.next_delay(state.interval);
+    }
+  }
+
+  /// Schedules the next crawl of the target without taking the last one into
+  /// account, e.g. because it has failed.
+  pub fn reschedule(&mut self, target: &Config, now: Instant) {
+    if let Some(state) = self.state(target) {
+      state.next_run = now + state.target.schedule.next_delay(state.interval);
+    }
+  }
+
+  /// The current interval of every target by its id.
+  pub fn intervals(&self) -> Vec<(String, Duration)> {
+    self
+      .targets
+      .iter()
+      .map(|state| (state.target.id(), state.interval))
+      .collect()
+  }
+
+  /// How long to wait until the next target will be due.
+  pub fn time_until_next_run(&self, now: Instant) -> Duration {
+    self
+      .targets
+      .iter()
+      .map(|state| state.next_run)
+      .min()
+      .map_or(Duration::from_secs(0), |next_run| {
+        next_run.saturating_duration_since(now)
+      })
+  }
+
+  fn state(&mut self, target: &Config) -> Option<&mut TargetState> {
+    let id = target.id();
+    self
+      .targets
+      .iter_mut()
+      .find(|state| state.target.id() == id)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ScheduleSettings, Scheduler, RECENT_CRAWLS};
+  use crate::crawlers::{Config, CrawlerImpl};
+  use crate::models::City;
+  use chrono::NaiveDate;
+  use std::time::{Duration, Instant};
+
+  fn target(path: &str, interval: u64, quiet_hours: Vec<&str>) -> Config {
+    let settings = ScheduleSettings {
+      interval: Some(interval),
+      jitter: Some(0),
+      quiet_hours: Some(quiet_hours.into_iter().map(|q| q.to_owned()).collect()),
+      ..Default::default()
+    };
+    Config {
+      host: "www.wg-gesucht.de".to_owned(),
+      path: path.to_owned(),
+      city: City::new("Munich"),
+      encoding: None,
+      crawler: CrawlerImpl::WGGesucht,
+      max_pages: 1,
+      stop_after_known: None,
+      details: false,
+      request: Default::default(),
+      crawl_timeout: Duration::from_secs(60),
+      schedule: settings.schedule().unwrap(),
+    }
+  }
+
+  #[test]
+  fn schedules_targets_by_interval() {
+    let start = Instant::now();
+    let noon = NaiveDate::from_ymd(2019, 10, 14).and_hms(12, 0, 0);
+    let mut scheduler = Scheduler::new(
+      vec![target("/often", 60, vec![]), target("/rarely", 600, vec![])],
+      start,
+    );
+
+    assert_eq!(scheduler.due(start, &noon).len(), 2);
+    for target in scheduler.due(start, &noon) {
+      scheduler.completed(&target, start, 1);
+    }
+    assert!(scheduler.due(start, &noon).is_empty());
+    assert_eq!(
+      scheduler.time_until_next_run(start),
+      Duration::from_secs(60)
+    );
+
+    let due = scheduler.due(start + Duration::from_secs(60), &noon);
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].path, "/often");
+  }
+
+  #[test]
+  fn skips_targets_during_quiet_hours() {
+    let start = Instant::now();
+    let day = NaiveDate::from_ymd(2019, 10, 14);
+    let scheduler = Scheduler::new(
+      vec![
+        target("/always", 60, vec![]),
+        target("/daytime", 60, vec!["* 0-6 * * *", "* 22-23 * * *"]),
+      ],
+      start,
+    );
+
+    assert_eq!(scheduler.due(start, &day.and_hms(3, 0, 0)).len(), 1);
+    assert_eq!(scheduler.due(start, &day.and_hms(23, 0, 0)).len(), 1);
+    assert_eq!(scheduler.due(start, &day.and_hms(7, 0, 0)).len(), 2);
+  }
+
+  #[test]
+  fn adapts_intervals_to_new_flats() {
+    let start = Instant::now();
+    let mut busy = target("/busy", 300, vec![]);
+    busy.schedule = ScheduleSettings {
+      interval: Some(300),
+      min_interval: Some(60),
+      max_interval: Some(3600),
+      jitter: Some(0),
+      ..Default::default()
+    }
+    .schedule()
+    .unwrap();
+    let mut quiet = busy.clone();
+    quiet.path = "/quiet".to_owned();
+    let mut scheduler = Scheduler::new(vec![busy.clone(), quiet.clone()], start);
+
+    scheduler.completed(&busy, start, 4);
+    for _ in 0..RECENT_CRAWLS {
+      scheduler.completed(&quiet, start, 0);
+    }
+    assert_eq!(
+      scheduler.intervals(),
+      vec![
+        (
+          "www.wg-gesucht.de/busy".to_owned(),
+          Duration::from_secs(150)
+        ),
+        (
+          "www.wg-gesucht.de/quiet".to_owned(),
+          Duration::from_secs(450)
+        ),
+      ]
+    );
+
+    for _ in 0..10 {
+      scheduler.completed(&busy, start, 10);
+    }
+    assert_eq!(scheduler.intervals()[0].1, Duration::from_secs(60));
+  }
+
+  #[test]
+  fn keeps_the_state_of_updated_targets() {
+    let start = Instant::now();
+    let noon = NaiveDate::from_ymd(2019, 10, 14).and_hms(12, 0, 0);
+    let mut kept = target("/kept", 600, vec![]);
+    kept.schedule.min_interval = Duration::from_secs(60);
+    let changed = target("/changed", 600, vec![]);
+    let mut scheduler = Scheduler::new(
+      vec![
+        kept.clone(),
+        changed.clone(),
+        target("/removed", 600, vec![]),
+      ],
+      start,
+    );
+    scheduler.completed(&kept, start, 5);
+    scheduler.completed(&changed, start, 0);
+
+    kept.max_pages = 2;
+    scheduler.update(
+      vec![
+        kept,
+        target("/changed", 60, vec![]),
+        target("/added", 600, vec![]),
+      ],
+      start,
+    );
+
+    assert_eq!(
+      scheduler.intervals(),
+      vec![
+        (
+          "www.wg-gesucht.de/kept".to_owned(),
+          Duration::from_secs(300)
+        ),
+        (
+          "www.wg-gesucht.de/changed".to_owned(),
+          Duration::from_secs(60)
+        ),
+        (
+          "www.wg-gesucht.de/added".to_owned(),
+          Duration::from_secs(600)
+        ),
+      ]
+    );
+    let due = scheduler.due(start, &noon);
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].path, "/added");
+    assert_eq!(
+      scheduler.due(start + Duration::from_secs(60), &noon).len(),
+      2
+    );
+  }
+
+  #[test]
+  fn keeps_intervals_of_failed_crawls() {
+    let start = Instant::now();
+    let target = target("/failing", 300, vec![]);
+    let mut scheduler = Scheduler::new(vec![target.clone()], start);
+
+    scheduler.reschedule(&target, start);
+    assert_eq!(
+      scheduler.time_until_next_run(start),
+      Duration::from_secs(300)
+    );
+  }
+
+  #[test]
+  fn adds_jitter() {
+    let schedule = ScheduleSettings {
+      interval: Some(60),
+      jitter: Some(10),
+      ..Default::default()
+    }
+    .schedule()
+    .unwrap();
+
+    for _ in 0..20 {
+      let delay = schedule.next_delay(schedule.interval);
+      assert!(delay >= Duration::from_secs(60) && delay <= Duration::from_secs(70));
+    }
+  }
+
+  #[test]
+  fn reports_invalid_schedules() {
+    let settings = ScheduleSettings {
+      interval: Some(0),
+      quiet_hours: Some(vec!["* 25 * * *".to_owned()]),
+      ..Default::default()
+    };
+    let no_min_interval = ScheduleSettings {
+      min_interval: Some(0),
+      ..Default::default()
+    };
+    let out_of_bounds = ScheduleSettings {
+      interval: Some(60),
+      min_interval: Some(120),
+      ..Default::default()
+    };
+
+    assert_eq!(settings.schedule().err().unwrap().len(), 2);
+    assert_eq!(
+      no_min_interval.schedule().err().unwrap(),
+      vec!["'min_interval' has to be at least 1 second"]
+    );
+    assert_eq!(
+      out_of_bounds.schedule().err().unwrap(),
+      vec!["'interval' (60) has to be between 'min_interval' (120) and 'max_interval' (60)"]
+    );
+  }
+}
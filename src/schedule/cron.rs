@@ -0,0 +1,171 @@
+extern crate chrono;
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use std::fmt;
+
+/// A cron expression with the five usual fields (minute, hour, day of month,
+/// month and day of week). Every field can be `*`, a number, a range `a-b`,
+/// a list `a,b` or a step `*/n` or `a-b/n`. Days of the week start with
+/// sunday as 0, which can also be written as 7. Like in the standard cron,
+/// a time matches either day field, once both of them are restricted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cron {
+  expression: String,
+  minutes: Vec<bool>,
+  hours: Vec<bool>,
+  days: Vec<bool>,
+  months: Vec<bool>,
+  weekdays: Vec<bool>,
+  /// whether both day fields are restricted, i.e. do not start with `*`
+  either_day: bool,
+}
+
+impl Cron {
+  pub fn parse(expression: &str) -> Result<Cron, String> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    if fields.len() != 5 {
+      return Err(format!(
+        "cron expression '{}' needs 5 fields, but has {}",
+        expression,
+        fields.len()
+      ));
+    }
+    let field = |index: usize, name: &str, min: u32, max: u32| {
+      parse_field(fields[index], min, max).map_err(|e| {
+        format!(
+          "cron expression '{}' has an invalid {}: {}",
+          expression, name, e
+        )
+      })
+    };
+    let mut weekdays = field(4, "day of week", 0, 7)?;
+    if weekdays[7] {
+      weekdays[0] = true;
+    }
+    Ok(Cron {
+      expression: expression.to_owned(),
+      minutes: field(0, "minute", 0, 59)?,
+      hours: field(1, "hour", 0, 23)?,
+      days: field(2, "day of month", 1, 31)?,
+      months: field(3, "month", 1, 12)?,
+      weekdays,
+      either_day: !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+    })
+  }
+
+  pub fn matches(&self, time: &NaiveDateTime) -> bool {
+    let day = self.days[time.day() as usize];
+    let weekday = self.weekdays[time.weekday().num_days_from_sunday() as usize];
+    self.minutes[time.minute() as usize]
+      && self.hours[time.hour() as usize]
+      && self.months[time.month() as usize]
+      && if self.either_day {
+        day || weekday
+      } else {
+        day && weekday
+      }
+  }
+}
+
+impl fmt::Display for Cron {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.expression)
+  }
+}
+
+/// Marks every value the field allows, indexed by the value itself.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>, String> {
+  let mut allowed = vec![false; max as usize + 1];
+  for part in field.split(',') {
+    let mut step_split = part.splitn(2, '/');
+    let range = step_split.next().unwrap_or("");
+    let step = match step_split.next() {
+      Some(step) => match step.parse::<u32>() {
+        Ok(step) if step > 0 => step,
+        _ => return Err(format!("'{}' is not a valid step", step)),
+      },
+      None => 1,
+    };
+    let (start, end) = if range == "*" {
+      (min, max)
+    } else {
+      let mut bounds = range.splitn(2, '-');
+      let start = parse_value(bounds.next().unwrap_or(""), min, max)?;
+      let end = match bounds.next() {
+        Some(end) => parse_value(end, min, max)?,
+        None if step > 1 => max,
+        None => start,
+      };
+      if end < start {
+        return Err(format!("'{}' is not a valid range", range));
+      }
+      (start, end)
+    };
+    for value in (start..=end).step_by(step as usize) {
+      allowed[value as usize] = true;
+    }
+  }
+  Ok(allowed)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, String> {
+  match value.parse::<u32>() {
+    Ok(number) if number >= min && number <= max => Ok(number),
+    _ => Err(format!(
+      "'{}' is not a number between {} and {}",
+      value, min, max
+    )),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Cron;
+  use chrono::NaiveDate;
+
+  #[test]
+  fn matches_nights() {
+    let nights = Cron::parse("* 0-5,23 * * *").unwrap();
+    let at = |hour, minute| NaiveDate::from_ymd(2019, 10, 14).and_hms(hour, minute, 0);
+
+    assert!(nights.matches(&at(23, 30)));
+    assert!(nights.matches(&at(3, 0)));
+    assert!(nights.matches(&at(5, 59)));
+    assert!(!nights.matches(&at(6, 0)));
+    assert!(!nights.matches(&at(12, 0)));
+  }
+
+  #[test]
+  fn matches_weekdays_and_steps() {
+    let sunday_mornings = Cron::parse("*/15 8-11 * * 7").unwrap();
+    let sunday = NaiveDate::from_ymd(2019, 10, 13);
+    let monday = NaiveDate::from_ymd(2019, 10, 14);
+
+    assert!(sunday_mornings.matches(&sunday.and_hms(9, 45, 0)));
+    assert!(!sunday_mornings.matches(&sunday.and_hms(9, 46, 0)));
+    assert!(!sunday_mornings.matches(&monday.and_hms(9, 45, 0)));
+  }
+
+  #[test]
+  fn matches_either_day_field() {
+    let firsts_and_sundays = Cron::parse("0 12 1 * 0").unwrap();
+    let firsts = Cron::parse("0 12 1 * *").unwrap();
+    let first_monday = NaiveDate::from_ymd(2019, 4, 1).and_hms(12, 0, 0);
+    let sunday = NaiveDate::from_ymd(2019, 10, 13).and_hms(12, 0, 0);
+    let monday = NaiveDate::from_ymd(2019, 10, 14).and_hms(12, 0, 0);
+
+    assert!(firsts_and_sundays.matches(&first_monday));
+    assert!(firsts_and_sundays.matches(&sunday));
+    assert!(!firsts_and_sundays.matches(&monday));
+    assert!(firsts.matches(&first_monday));
+    assert!(!firsts.matches(&sunday));
+  }
+
+  #[test]
+  fn rejects_invalid_expressions() {
+    assert!(Cron::parse("* * * *").is_err());
+    assert!(Cron::parse("* 25 * * *").is_err());
+    assert!(Cron::parse("* 6-2 * * *").is_err());
+    assert!(Cron::parse("*/0 * * * *").is_err());
+  }
+}
@@ -0,0 +1,139 @@
+extern crate signal_hook;
+
+use crate::crawlers::Cancellation;
+use std::io;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// how often a shutdown that is under way looks for a second signal
+const POLL_INTERVAL_IN_MILLIS: u64 = 100;
+
+/// Shared by everything that handles signals.
+#[derive(Debug)]
+pub struct Error {
+  pub message: String,
+}
+
+impl From<io::Error> for Error {
+  fn from(err: io::Error) -> Error {
+    Error {
+      message: format!("Signal Error: {}", err),
+    }
+  }
+}
+
+/// Lets the crawler stop gracefully: once a shutdown has been requested, no
+/// further crawls will be started and the running ones are cancelled after
+/// a grace period. Clones share their state.
+#[derive(Clone, Debug, Default)]
+pub struct Shutdown {
+  /// is triggered by the shutdown request itself
+  requested: Cancellation,
+  /// set by every signal, to notice a second one after the request
+  signalled: Arc<AtomicBool>,
+  cancellation: Cancellation,
+}
+
+impl Shutdown {
+  pub fn new() -> Shutdown {
+    Default::default()
+  }
+
+  /// Requests a shutdown as soon as the process receives SIGTERM or SIGINT.
+  pub fn on_signals(&self) -> Result<(), Error> {
+    for signal in &[signal_hook::SIGTERM, signal_hook::SIGINT] {
+      signal_hook::flag::register(*signal, self.requested.flag())?;
+      signal_hook::flag::register(*signal, self.signalled.clone())?;
+    }
+    Ok(())
+  }
+
+  pub fn request(&self) {
+    self.requested.cancel();
+  }
+
+  pub fn is_requested(&self) -> bool {
+    self.requested.is_cancelled()
+  }
+
+  /// Is triggered as soon as a shutdown has been requested, e.g. to stop
+  /// trying things again.
+  pub fn requested(&self) -> Cancellation {
+    self.requested.clone()
+  }
+
+  /// Cancels all crawls, once the grace period after a shutdown request has
+  /// passed.
+  pub fn cancellation(&self) -> Cancellation {
+    self.cancellation.clone()
+  }
+
+  /// Watches for a shutdown request in the background and cancels the
+  /// running crawls `grace_period` after it.
+  pub fn cancel_after(&self, grace_period: Duration) {
+    let shutdown = self.clone();
+    thread::spawn(move || {
+      while !shutdown.sleep(Duration::from_secs(60)) {}
+      thread::sleep(grace_period);
+      shutdown.cancellation.cancel();
+    });
+  }
+
+  /// Exits the process once a shutdown has taken longer than `deadline`, or
+  /// right away on a second signal. Crawls only notice their cancellation
+  /// between requests, so they could otherwise keep the process alive for a
+  /// long time.
+  pub fn exit_after(&self, deadline: Duration) {
+    let shutdown = self.clone();
+    thread::spawn(move || {
+      while !shutdown.sleep(Duration::from_secs(60)) {}
+      shutdown.signalled.store(false, Ordering::SeqCst);
+      let end = Instant::now() + deadline;
+      while Instant::now() < end {
+        if shutdown.signalled.load(Ordering::SeqCst) {
+          eprintln!("received a second signal, exiting right away.");
+          process::exit(1);
+        }
+        thread::sleep(Duration::from_millis(POLL_INTERVAL_IN_MILLIS));
+      }
+      eprintln!(
+        "could not shut down within {} seconds, exiting right away.",
+        deadline.as_secs()
+      );
+      process::exit(1);
+    });
+  }
+
+  /// Sleeps for the given duration, but wakes up early when a shutdown is
+  /// requested. Returns whether it has been.
+  pub fn sleep(&self, duration: Duration) -> bool {
+    self.requested.sleep(duration)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Shutdown;
+  use std::thread;
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn cancels_crawls_after_grace_period() {
+    let shutdown = Shutdown::new();
+    shutdown.cancel_after(Duration::from_millis(200));
+    let cancellation = shutdown.cancellation();
+
+    assert!(!shutdown.sleep(Duration::from_millis(50)));
+    let start = Instant::now();
+    shutdown.clone().request();
+    assert!(shutdown.sleep(Duration::from_secs(60)));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(!cancellation.is_cancelled());
+
+    thread::sleep(Duration::from_millis(500));
+    assert!(cancellation.is_cancelled());
+  }
+}
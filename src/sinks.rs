@@ -0,0 +1,230 @@
+mod amqp;
+mod jsonl;
+mod stdout;
+mod webhook;
+
+pub use crate::sinks::amqp::AmqpConfig;
+pub use crate::sinks::amqp::AmqpSink;
+pub use crate::sinks::amqp::ExchangeKind;
+pub use crate::sinks::jsonl::JsonLinesSink;
+pub use crate::sinks::stdout::StdoutSink;
+pub use crate::sinks::webhook::WebhookSink;
+
+use crate::crawlers::Cancellation;
+use crate::models::Flat;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::io;
+
+#[derive(Debug)]
+pub struct Error {
+  pub message: String,
+}
+
+impl From<serde_json::Error> for Error {
+  fn from(err: serde_json::Error) -> Error {
+    Error {
+      message: format!("Could not serialize flat: {}", err),
+    }
+  }
+}
+
+impl From<io::Error> for Error {
+  fn from(err: io::Error) -> Error {
+    Error {
+      message: format!("IO Error: {}", err),
+    }
+  }
+}
+
+impl From<reqwest::Error> for Error {
+  fn from(err: reqwest::Error) -> Error {
+    Error {
+      message: format!("Request Error: {}", err),
+    }
+  }
+}
+
+impl From<lapin::Error> for Error {
+  fn from(err: lapin::Error) -> Error {
+    Error {
+      message: format!("AMQP Error: {}", err),
+    }
+  }
+}
+
+impl From<native_tls::Error> for Error {
+  fn from(err: native_tls::Error) -> Error {
+    Error {
+      message: format!("TLS Error: {}", err),
+    }
+  }
+}
+
+/// Where new flats are passed on to.
+pub trait Sink {
+  /// Tells the sinks apart in the logs.
+  fn name(&self) -> String;
+
+  /// Passes on the flats, all of them have been taken once it returns `Ok`.
+  fn send(&mut self, flats: &[Flat]) -> Result<(), Error>;
+
+  /// Passes on the flats like `send`, but a sink that may take some of them
+  /// while failing on the others tells which ones it has taken, so that only
+  /// the others are sent again. By default, a failed batch has not been taken
+  /// at all.
+  fn send_some(&mut self, flats: &[Flat]) -> Result<(), Undelivered> {
+    self.send(flats).map_err(|error| Undelivered {
+      taken: Vec::new(),
+      error,
+    })
+  }
+}
+
+/// Why a sink has not taken all flats of a batch.
+#[derive(Debug)]
+pub struct Undelivered {
+  /// the positions of the flats within the batch that have been taken
+  /// nonetheless
+  pub taken: Vec<usize>,
+  pub error: Error,
+}
+
+/// A `[[sink]]` entry, its `type` tells which kind of sink it is.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkConfig {
+  /// publishes every flat to an AMQP exchange
+  Amqp(Box<AmqpConfig>),
+  /// prints every flat as a line of JSON
+  Stdout,
+  /// appends every flat as a line of JSON to the file at `path`
+  Jsonl { path: String },
+  /// posts the flats as a JSON array to `url`
+  Webhook {
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// in seconds
+    timeout: Option<u64>,
+  },
+}
+
+impl SinkConfig {
+  /// The `type` of the sink.
+  pub fn kind(&self) -> &'static str {
+    match self {
+      SinkConfig::Amqp(_) => "amqp",
+      SinkConfig::Stdout => "stdout",
+      SinkConfig::Jsonl { .. } => "jsonl",
+      SinkConfig::Webhook { .. } => "webhook",
+    }
+  }
+
+  pub fn validate(&self) -> Vec<String> {
+    let mut errors = Vec::new();
+    match self {
+      SinkConfig::Amqp(amqp_config) => {
+        for key in amqp_config.missing_keys() {
+          errors.push(format!("'{}' is missing", key));
+        }
+        errors.extend(amqp_config.validate());
+      }
+      SinkConfig::Jsonl { path } if path.is_empty() => {
+        errors.push("'path' must not be empty".to_owned())
+      }
+      SinkConfig::Webhook { url, timeout, .. } => {
+        if let Err(e) = url::Url::parse(url) {
+          errors.push(format!("'url' is not a valid URL: {}", e));
+        }
+        if *timeout == Some(0) {
+          errors.push("'timeout' has to be at least 1 second".to_owned());
+        }
+      }
+      _ => {}
+    }
+    errors
+  }
+}
+
+/// The sinks along with the ids their outboxes are kept by.
+pub type Sinks = Vec<(String, Box<dyn Sink>)>;
+
+/// A sink along with the id that its outbox is kept by.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SinkEntry {
+  /// has to be unique and stays the same while the sink itself is changed,
+  /// so that the flats in its outbox are kept, defaults to the `type`
+  pub id: Option<String>,
+  #[serde(flatten)]
+  pub config: SinkConfig,
+}
+
+impl SinkEntry {
+  pub fn id(&self) -> &str {
+    self.id.as_deref().unwrap_or_else(|| self.config.kind())
+  }
+}
+
+/// Creates the configured sinks along with their ids, in test mode AMQP
+/// flats are published to a separate exchange and queue. Sinks that try
+/// batches again stop doing so once `cancellation` has been triggered.
+pub fn create(
+  entries: &[SinkEntry],
+  test: bool,
+  cancellation: &Cancellation,
+) -> Result<Sinks, Error> {
+  let mut sinks: Sinks = Vec::new();
+  for entry in entries {
+    let sink: Box<dyn Sink> = match &entry.config {
+      SinkConfig::Amqp(amqp_config) => Box::new(AmqpSink::new(amqp_config, test, cancellation)?),
+      SinkConfig::Stdout => Box::new(StdoutSink {}),
+      SinkConfig::Jsonl { path } => Box::new(JsonLinesSink::open(path)?),
+      SinkConfig::Webhook {
+        url,
+        headers,
+        timeout,
+      } => Box::new(WebhookSink::new(url, headers, *timeout)?),
+    };
+    sinks.push((entry.id().to_owned(), sink));
+  }
+  Ok(sinks)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::SinkConfig;
+  use config::{Config, File, FileFormat};
+
+  #[test]
+  fn reads_sink_configs() {
+    let mut config = Config::new();
+    config
+      .merge(File::from_str(
+        r#"
+        [[sink]]
+        type = "stdout"
+
+        [[sink]]
+        type = "jsonl"
+        path = "flats.jsonl"
+
+        [[sink]]
+        type = "webhook"
+        url = "not a url"
+        "#,
+        FileFormat::Toml,
+      ))
+      .unwrap();
+    let sinks: Vec<SinkConfig> = config.get("sink").unwrap();
+
+    assert_eq!(sinks[0], SinkConfig::Stdout);
+    assert_eq!(
+      sinks[1],
+      SinkConfig::Jsonl {
+        path: "flats.jsonl".to_owned()
+      }
+    );
+    assert_eq!(sinks[2].validate().len(), 1);
+  }
+}
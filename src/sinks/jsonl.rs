@@ -0,0 +1,74 @@
+use crate::models::Flat;
+use crate::sinks::{Error, Sink};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+/// Appends every flat as a line of JSON to a file.
+pub struct JsonLinesSink {
+  path: String,
+  file: File,
+}
+
+impl JsonLinesSink {
+  /// Opens the file for appending, it is created if it does not exist yet.
+  pub fn open(path: &str) -> Result<JsonLinesSink, Error> {
+    let file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(path)
+      .map_err(|e| Error {
+        message: format!("could not open '{}': {}", path, e),
+      })?;
+    Ok(JsonLinesSink {
+      path: path.to_owned(),
+      file,
+    })
+  }
+}
+
+impl Sink for JsonLinesSink {
+  fn name(&self) -> String {
+    format!("file '{}'", self.path)
+  }
+
+  fn send(&mut self, flats: &[Flat]) -> Result<(), Error> {
+    // written at once, so that no flat is split up by a failing write
+    let mut lines = String::new();
+    for flat in flats {
+      lines.push_str(&serde_json::to_string(flat)?);
+      lines.push('\n');
+    }
+    self.file.write_all(lines.as_bytes())?;
+    self.file.sync_data()?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::JsonLinesSink;
+  use crate::models::{City, Flat};
+  use crate::sinks::Sink;
+  use std::env;
+  use std::fs;
+  use std::slice;
+
+  #[test]
+  fn appends_flats() {
+    let path = env::temp_dir().join("flatcrawl-jsonl-test.jsonl");
+    let _ = fs::remove_file(&path);
+    let flat = Flat::new("wggesucht".to_owned(), City::new("Munich"));
+
+    for _ in 0..2 {
+      let mut sink = JsonLinesSink::open(path.to_str().unwrap()).unwrap();
+      sink.send(slice::from_ref(&flat)).unwrap();
+    }
+    let lines = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(lines.lines().count(), 2);
+    assert!(lines
+      .lines()
+      .all(|line| line.contains("\"source\":\"wggesucht\"")));
+  }
+}
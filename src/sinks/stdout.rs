@@ -0,0 +1,18 @@
+use crate::models::Flat;
+use crate::sinks::{Error, Sink};
+
+/// Prints every flat as a line of JSON, e.g. to be piped into other tools.
+pub struct StdoutSink {}
+
+impl Sink for StdoutSink {
+  fn name(&self) -> String {
+    "stdout".to_owned()
+  }
+
+  fn send(&mut self, flats: &[Flat]) -> Result<(), Error> {
+    for flat in flats {
+      println!("{}", serde_json::to_string(flat)?);
+    }
+    Ok(())
+  }
+}
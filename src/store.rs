@@ -0,0 +1,181 @@
+extern crate serde_json;
+extern crate sled;
+
+use crate::models::Flat;
+
+#[derive(Debug)]
+pub struct Error {
+  pub message: String,
+}
+
+impl From<sled::Error> for Error {
+  fn from(err: sled::Error) -> Error {
+    Error {
+      message: format!("Store Error: {}", err),
+    }
+  }
+}
+
+impl From<serde_json::Error> for Error {
+  fn from(err: serde_json::Error) -> Error {
+    Error {
+      message: format!("Could not serialize flat: {}", err),
+    }
+  }
+}
+
+/// Persists every flat that has been seen, so that it survives restarts.
+/// Besides, the outbox keeps the flats that have not been delivered to a
+/// sink yet.
+///
+/// Flats are keyed by city, source and external id.
+pub struct Store {
+  db: sled::Db,
+  flats: sled::Tree,
+  outbox: sled::Tree,
+}
+
+impl Store {
+  pub fn open(path: &str) -> Result<Store, Error> {
+    Store::from_db(sled::open(path)?)
+  }
+
+  fn from_db(db: sled::Db) -> Result<Store, Error> {
+    let flats = db.open_tree("flats")?;
+    let outbox = db.open_tree("outbox")?;
+    Ok(Store { db, flats, outbox })
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.flats.is_empty()
+  }
+
+  pub fn flats(&self) -> Result<Vec<Flat>, Error> {
+    let mut flats = Vec::new();
+    for entry in self.flats.iter() {
+      let (_, value) = entry?;
+      flats.push(serde_json::from_slice(&value)?);
+    }
+    Ok(flats)
+  }
+
+  pub fn remember(&self, flat: &Flat) -> Result<(), Error> {
+    if let Some(key) = id_key(flat) {
+      self
+        .flats
+        .insert(key.as_bytes(), serde_json::to_vec(flat)?)?;
+    }
+    Ok(())
+  }
+
+  /// Puts the flats into the outboxes of all the sinks at once, where they
+  /// stay until they have been delivered. Flats without data cannot be told
+  /// apart and are left out.
+  pub fn enqueue(&self, sinks: &[&str], flats: &[Flat]) -> Result<(), Error> {
+    let mut batch = sled::Batch::default();
+    for flat in flats {
+      if let Some(key) = id_key(flat) {
+        let value = serde_json::to_vec(flat)?;
+        for sink in sinks {
+          batch.insert(outbox_key(sink, &key), value.clone());
+        }
+      }
+    }
+    self.outbox.apply_batch(batch)?;
+    self.flush()
+  }
+
+  /// The flats that are waiting to be delivered to the sink.
+  pub fn pending(&self, sink: &str) -> Result<Vec<Flat>, Error> {
+    let mut flats = Vec::new();
+    for entry in self.outbox.scan_prefix(outbox_key(sink, "")) {
+      let (_, value) = entry?;
+      flats.push(serde_json::from_slice(&value)?);
+    }
+    Ok(flats)
+  }
+
+  /// Takes the flats out of the outbox of the sink, once it has confirmed
+  /// them.
+  pub fn delivered(&self, sink: &str, flats: &[Flat]) -> Result<(), Error> {
+    for flat in flats {
+      if let Some(key) = id_key(flat) {
+        self.outbox.remove(outbox_key(sink, &key))?;
+      }
+    }
+    self.flush()
+  }
+
+  pub fn flush(&self) -> Result<(), Error> {
+    self.db.flush()?;
+    Ok(())
+  }
+}
+
+fn id_key(flat: &Flat) -> Option<String> {
+  flat
+    .data
+    .as_ref()
+    .map(|data| format!("{}\0{}\0{}", flat.city, flat.source, data.externalid))
+}
+
+fn outbox_key(sink: &str, id_key: &str) -> Vec<u8> {
+  format!("{}\0{}", sink, id_key).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Store;
+  use crate::models::{City, Flat, FlatData};
+
+  fn temporary_store() -> Store {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    Store::from_db(db).unwrap()
+  }
+
+  fn flat(source: &str, externalid: &str) -> Flat {
+    Flat::new(source.to_owned(), City::new("Munich")).fill(&FlatData {
+      rent: 100.,
+      squaremeters: 50.,
+      address: String::from("Some address"),
+      title: String::from("Some title"),
+      externalid: String::from(externalid),
+      rooms: 2.,
+      ..Default::default()
+    })
+  }
+
+  #[test]
+  fn remembers_flats() {
+    let store = temporary_store();
+    assert!(store.is_empty());
+    store.remember(&flat("immoscout", "1")).unwrap();
+    store.remember(&flat("immoscout", "1")).unwrap();
+    store.remember(&flat("immowelt", "1")).unwrap();
+    assert!(!store.is_empty());
+
+    let flats = store.flats().unwrap();
+    assert_eq!(flats.len(), 2);
+    assert_eq!(flats[0].source, "immoscout");
+    assert_eq!(flats[1].source, "immowelt");
+  }
+
+  #[test]
+  fn keeps_flats_until_they_have_been_delivered() {
+    let store = temporary_store();
+    let flats = [flat("immoscout", "1"), flat("immowelt", "2")];
+    store.enqueue(&["amqp", "stdout"], &flats[..1]).unwrap();
+    store.enqueue(&["amqp", "stdout"], &flats[..1]).unwrap();
+    store.enqueue(&["amqp"], &flats[1..]).unwrap();
+
+    assert_eq!(store.pending("amqp").unwrap().len(), 2);
+    assert_eq!(store.pending("stdout").unwrap().len(), 1);
+    assert!(store.pending("amq").unwrap().is_empty());
+
+    store.delivered("amqp", &flats[1..]).unwrap();
+    let pending = store.pending("amqp").unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].source, "immoscout");
+    assert_eq!(store.pending("stdout").unwrap().len(), 1);
+  }
+}